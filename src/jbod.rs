@@ -1,2 +1,5 @@
 pub mod disks;
 pub mod enclosure;
+pub mod scsi;
+pub mod ses;
+pub mod slotmap;
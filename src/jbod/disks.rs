@@ -30,546 +30,709 @@
 
 #[allow(non_snake_case)]
 pub mod DiskShelf {
-    use clap::ArgMatches;
-    use colored::*;
+    use serde::Serialize;
     use std::collections::HashMap;
     use std::fs;
-    use std::io::{BufRead, BufReader};
-    use std::process::{exit, Command, Stdio};
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use std::thread;
 
     use crate::jbod::enclosure::BackPlane;
+    use crate::jbod::scsi::Scsi;
+    use crate::jbod::ses::Ses;
+    use crate::jbod::ses::Ses::{AdditionalStatus, SlotStatus};
+    use crate::utils::error::Error::JbodError;
     use crate::utils::helper::Util;
-    use crate::utils::helper::Util::{SCSI_TEMP, SGINFO, SG_MAP};
+    use crate::utils::mdstat::MdStat::{MdArray, MdMember};
+    use crate::utils::runner::Runner::Context;
 
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Default, Serialize)]
     pub struct Disk {
-        // Enclosure number identification, example: 15:0:1:0
+        /// Enclosure number identification, example: 15:0:1:0
         pub enclosure: String,
-        // Disk slopt identification
-        pub slot: String,
-        // Disk path, example: /dev/sg105
+        /// Disk slot number in the enclosure
+        pub slot: u32,
+        /// Disk path, example: /dev/sg105
         pub device_path: String,
-        // Disk map, example: /dev/sdcz
-        pub device_map: String,
-        // Disk temperature
-        pub temperature: String,
-        // Disk vendor
+        /// Disk map, example: /dev/sdcz, `None` when the disk has no block device
+        pub device_map: Option<String>,
+        /// Disk temperature in Celsius, `None` when the disk cannot report it
+        pub temperature: Option<i32>,
+        /// Disk vendor
         pub vendor: String,
-        // Disk model
+        /// Disk model
         pub model: String,
-        // Disk serial number
-        pub serial: String,
-        // Disk firmware revision
+        /// Disk serial number, `None` when the disk does not report it
+        pub serial: Option<String>,
+        /// Disk firmware revision
         pub fw_revision: String,
-        // Path to led control file
-        pub led_locate_path: String,
-        // Path to led control file
-        pub led_fault_path: String,
+        /// Disk NAA identifier, example: 0x5000cca26b1ac3a8
+        pub wwn: Option<String>,
+        /// Path to the locate led control file, `None` when the slot has no led
+        pub led_locate_path: Option<PathBuf>,
+        /// Path to the fault led control file, `None` when the slot has no led
+        pub led_fault_path: Option<PathBuf>,
+        /// The state bits of the slot element, `None` until the enclosure pages
+        /// are read by `get_enclosure_slots` or `jbod_inventory`
+        pub flags: Option<SlotStatus>,
     }
 
-    /// Returns a string with the temperature
+    /// Returns the temperature in Celsius
     ///
-    /// This function is a wrapper over scsi_temperature script.
+    /// This function reads the temperature log page with LOG SENSE, it
+    /// returns `None` if the disk cannot report it.
     ///
     /// # Argumets
     ///
+    /// * `ctx` - the context used to send LOG SENSE
     /// * `disk` - a string with the device path
     ///
     /// # Example
-    /// ```
-    /// let temperature = get_disk_temperature("/dev/sg100");
+    /// ```ignore
+    /// let temperature = get_disk_temperature(&ctx, "/dev/sg100");
     /// ```
     ///
-    fn get_disk_temperature(disk: String) -> String {
-        let scsi_temp_cmd = Command::new(SCSI_TEMP)
-            .args(&[disk])
-            .output()
-            .expect("Failed to scsi_temperature the device");
-        let scsi_temp_output = String::from_utf8_lossy(&scsi_temp_cmd.stdout);
-        let output_spl: Vec<&str> = scsi_temp_output.split('\n').collect();
-        let temperature: String = output_spl[2].chars().filter(|n| n.is_digit(10)).collect();
-
-        temperature
+    fn get_disk_temperature(ctx: &Context, disk: &str) -> Option<i32> {
+        Scsi::read_temperature(ctx, disk)
+            .ok()
+            .and_then(|temperature| temperature.current)
     }
 
-    /// Returns a string with the disk firmware version
+    /// Returns the slot number of an enclosure component
     ///
-    /// This function is a wrapper over sginfo script.
+    /// The kernel exposes the number in the `slot` attribute, older kernels
+    /// only have the component name, example: "SLOT 012,3FJ1ABCD" or "12".
     ///
-    /// # Argumets
-    ///
-    /// * `disk` - a string with the device path
+    /// # Arguments
     ///
-    /// # Example
-    /// ```
-    /// let fw_revision = get_disk_firmware("/dev/sg100");
-    /// ```
+    /// * `component` - the sysfs directory of the enclosure component
     ///
-    fn get_disk_firmware(disk: String) -> String {
-        let mut fw_revision = String::new();
-        let sginfo_temp_cmd = Command::new(SGINFO)
-            .args(&[disk])
-            .output()
-            .expect("Failed to sginfo the device");
-        let sginfo_temp_output = String::from_utf8_lossy(&sginfo_temp_cmd.stdout);
-
-        for fw_info in sginfo_temp_output.split('\n') {
-            if fw_info.contains("Revision level") {
-                fw_revision = fw_info.replace("Revision level:", "").trim().to_string();
-                break;
+    fn get_disk_slot(component: &Path) -> Option<u32> {
+        if let Ok(slot) = fs::read_to_string(component.join("slot")) {
+            if let Ok(slot) = slot.trim().parse() {
+                return Some(slot);
             }
         }
 
-        fw_revision
+        let name = component.file_name()?.to_string_lossy().to_string();
+        let digits: String = name
+            .split(',')
+            .next()?
+            .chars()
+            .skip_while(|c| !c.is_ascii_digit())
+            .take_while(|c| c.is_ascii_digit())
+            .collect();
+        digits.parse().ok()
     }
-    /// Returns a string with the disk serial number
+
+    /// Returns the block device of a scsi generic device, example: /dev/sddk
+    /// for sg116, or `None` if the device has no block device.
     ///
     /// # Arguments
     ///
-    /// * `disk` - a string with the device path
-    ///
-    /// # Example
-    /// ```
-    /// let serial = get_disk_serial("/dev/sg100");
-    /// ```
-    ///
-    fn get_disk_serial(disk: String) -> String {
-        let res = fs::read(disk + "/vpd_pg80");
-        let content = match res {
-            Ok(c) => c.to_vec(),
-            Err(_err) => "N/A".as_bytes().to_vec(),
-        };
-
-        unsafe { String::from_utf8_unchecked(content.to_vec()).to_string() }
+    /// * `ctx` - the context holding the sysfs root
+    /// * `sg_name` - the scsi generic device name, example: sg116
+    ///
+    fn get_disk_block_device(ctx: &Context, sg_name: &str) -> Option<String> {
+        let block = ctx
+            .sys_class("scsi_generic")
+            .join(sg_name)
+            .join("device")
+            .join("block");
+
+        fs::read_dir(block)
+            .ok()?
+            .filter_map(Result::ok)
+            .map(|entry| format!("/dev/{}", entry.file_name().to_string_lossy()))
+            .next()
     }
 
-    /// Returns a string with the disk vendor
+    /// Returns the path of a led file of an enclosure component, or `None`
+    /// if the enclosure does not provide led support via file
     ///
     /// # Arguments
     ///
-    /// * `disk` - a string with the device path
+    /// * `component` - the sysfs directory of the enclosure component
+    /// * `led` - the led file name, "locate" or "fault"
     ///
-    /// # Example
-    /// ```
-    /// let serial = get_disk_vendor("/dev/sg100");
-    /// ```
-    ///
-    fn get_disk_vendor(disk: String) -> String {
-        let res = fs::read(disk + "/vendor");
-        let content = match res {
-            Ok(c) => c.to_vec(),
-            Err(_err) => "N/A".as_bytes().to_vec(),
-        };
+    fn get_disk_led_path(component: &Path, led: &str) -> Option<PathBuf> {
+        let led_path = component.join(led);
 
-        unsafe {
-            let mut s = String::from_utf8_unchecked(content.to_vec()).to_string();
-            s.truncate(s.len() - 1);
-            s
+        if Util::path_exists(&led_path) {
+            Some(led_path)
+        } else {
+            None
         }
     }
 
-    /// Returns a string with the disk model
+    /// Returns the disk matching a device path or map, example: /dev/sg105
+    /// or /dev/sdcz
     ///
     /// # Arguments
     ///
-    /// * `disk` - a string with the device path
-    ///
-    /// # Example
-    /// ```
-    /// let serial = get_disk_model("/dev/sg100");
-    /// ```
+    /// * `ctx` - the context used to find the disk
+    /// * `disk` - the device path or map of the disk
     ///
-    fn get_disk_model(disk: String) -> String {
-        let res = fs::read(disk + "/model");
-        let content = match res {
-            Ok(c) => c.to_vec(),
-            Err(_err) => "N/A".as_bytes().to_vec(),
-        };
+    fn find_disk(ctx: &Context, disk: &str) -> Result<Disk, JbodError> {
+        if !Util::path_exists(disk) {
+            return Err(JbodError::DeviceVanished(disk.to_string()));
+        }
 
-        unsafe {
-            let mut s = String::from_utf8_unchecked(content.to_vec()).to_string();
-            s.truncate(s.len() - 1);
-            s
+        jbod_disk_map(ctx)?
+            .into_iter()
+            .find(|v| (v.device_path == disk) || (v.device_map.as_deref() == Some(disk)))
+            .ok_or_else(|| JbodError::InvalidArgument(format!("{} is not in any enclosure", disk)))
+    }
+
+    /// The state requested for a disk bay led.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum LedState {
+        On,
+        Off,
+    }
+
+    impl LedState {
+        /// Returns the value written into the sysfs led file.
+        pub fn sysfs_value(&self) -> &'static str {
+            match self {
+                LedState::On => "1",
+                LedState::Off => "0",
+            }
         }
     }
 
-    /// Returns a HashMap with two strings, example: /dev/sg116 and /dev/sddk
+    /// Here we write 0 or 1 into the disk led file
     ///
-    /// This function is a wraper over sg_map
+    /// # Arguments
     ///
-    fn get_disk_sd_map() -> HashMap<String, String> {
-        let mut disks: HashMap<String, String> = HashMap::new();
-        let mut sg_map_cmd = Command::new(SG_MAP).stdout(Stdio::piped()).spawn().unwrap();
+    /// * `led_path` - the led file of the disk, `None` if the slot has no led
+    /// * `disk` - the device path or map of the disk, used in the errors
+    /// * `led` - the led name, "locate" or "fault"
+    /// * `option` - "1" to turn the led on, "0" to turn it off
+    ///
+    fn write_disk_led(
+        led_path: Option<&PathBuf>,
+        disk: &str,
+        led: &str,
+        option: &str,
+    ) -> Result<(), JbodError> {
+        let led_path = led_path.ok_or_else(|| {
+            JbodError::InvalidArgument(format!("{} does not expose {} led", disk, led))
+        })?;
+
+        fs::write(led_path, option)
+            .map_err(|err| JbodError::from_io(led_path.to_string_lossy(), err))
+    }
 
-        {
-            let stdout = sg_map_cmd.stdout.as_mut().unwrap();
-            let stdout_reader = BufReader::new(stdout);
-            let stdout_lines = stdout_reader.lines();
-
-            for line in stdout_lines {
-                let line_unwrap = line.unwrap().to_owned();
-                let output_split: Vec<&str> = line_unwrap.split_whitespace().collect();
-                if output_split.len() > 1 {
-                    disks.insert(output_split[0].to_string(), output_split[1].to_string());
-                } else {
-                    disks.insert(output_split[0].to_string(), "NONE".to_string());
-                }
-            }
-        }
-        sg_map_cmd.wait().unwrap();
+    /// Returns the disk whose locate led was switched
+    ///
+    /// Here we write 0 or 1 into the disk led file
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to find the disk
+    /// * `disk` - the device path or map of the disk, example: /dev/sg105 or /dev/sdcz
+    /// * `state` - turns the locate led on or off
+    ///
+    pub fn set_disk_led_locate(ctx: &Context, disk: &str, state: LedState) -> Result<Disk, JbodError> {
+        let found_disk = find_disk(ctx, disk)?;
+        write_disk_led(
+            found_disk.led_locate_path.as_ref(),
+            disk,
+            "locate",
+            state.sysfs_value(),
+        )?;
 
-        disks
+        Ok(found_disk)
     }
 
-    /// Returns a string with the led file location or NONE
+    /// Returns the disk whose fault led was switched
     ///
-    /// This function tries to localize if the enclosure provide led support via file
+    /// Here we write 0 or 1 into the disk led file
     ///
     /// # Arguments
     ///
-    /// * `enclosure_slot` - a string reference with the enclosure_slot identification
-    /// * `disk_slot` - a string reference with the disk slot number identification
+    /// * `ctx` - the context used to find the disk
+    /// * `disk` - the device path or map of the disk, example: /dev/sg105 or /dev/sdcz
+    /// * `state` - turns the fault led on or off
     ///
-    fn get_disk_led_locate_path(enclosure_slot: &str, disk_slot: &str) -> String {
-        let sys_class_enclosure: &str = "/sys/class/enclosure/";
+    pub fn set_disk_led_fault(ctx: &Context, disk: &str, state: LedState) -> Result<Disk, JbodError> {
+        let found_disk = find_disk(ctx, disk)?;
+        write_disk_led(
+            found_disk.led_fault_path.as_ref(),
+            disk,
+            "fault",
+            state.sysfs_value(),
+        )?;
 
-        Util::verify_sysclass_folder(sys_class_enclosure);
+        Ok(found_disk)
+    }
 
-        let led_locate_path = sys_class_enclosure.to_string()
-            + enclosure_slot
-            + &"/".to_string()
-            + &disk_slot.to_string()
-            + &"/locate".to_string();
+    /// Returns true if an enclosure component is a disk slot, the name
+    /// depends on the enclosure, example: "SLOT 012,3FJ1ABCD", "Disk001" or "12".
+    ///
+    /// # Arguments
+    ///
+    /// * `component` - the sysfs path of the enclosure component
+    ///
+    fn is_slot_component(component: &Path) -> bool {
+        let name = match component.file_name() {
+            Some(name) => name.to_string_lossy().to_lowercase(),
+            None => return false,
+        };
 
-        if Util::path_exists(&led_locate_path) {
-            return led_locate_path;
-        } else {
-            return "NONE".to_string();
+        name.contains("slot")
+            || name.contains("disk")
+            || name.contains("array device")
+            || name.bytes().all(|c| c.is_ascii_digit())
+    }
+
+    /// Returns the Disk structure of an enclosure component, or `None` if
+    /// the component is not a disk slot or has no disk plugged.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to probe the disk
+    /// * `component` - the sysfs path of the enclosure component
+    /// * `enclosure_slot` - the enclosure slot number, example: 15:0:0:0
+    ///
+    fn get_disk_details(ctx: &Context, component: &Path, enclosure_slot: &str) -> Option<Disk> {
+        if !is_slot_component(component) {
+            return None;
         }
+
+        let slot = get_disk_slot(component)?;
+        probe_disk(ctx, &component.join("device"), enclosure_slot, slot, Some(component))
     }
 
-    /// Returns a string with the led file location or NONE
+    /// Returns the Disk structure of a scsi device, or `None` if it has no
+    /// scsi generic device.
     ///
-    /// This function tries to localize if the enclosure provide led support via file
+    /// This function gets all details of a disk.
     ///
     /// # Arguments
     ///
-    /// * `enclosure_slot` - a string reference with the enclosure_slot identification
-    /// * `disk_slot` - a string reference with the disk slot number identification
+    /// * `ctx` - the context used to probe the disk
+    /// * `generic_device` - the sysfs path of the scsi device, example: .../1:0:5:0
+    /// * `enclosure_slot` - the enclosure slot number, example: 15:0:0:0
+    /// * `slot` - the slot number of the disk in the enclosure
+    /// * `component` - the sysfs path of the enclosure component holding the leds
+    ///
+    fn probe_disk(
+        ctx: &Context,
+        generic_device: &Path,
+        enclosure_slot: &str,
+        slot: u32,
+        component: Option<&Path>,
+    ) -> Option<Disk> {
+        let sg_name = fs::read_dir(generic_device.join("scsi_generic"))
+            .ok()?
+            .filter_map(Result::ok)
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .last()?;
+        let device_path = format!("/dev/{}", sg_name);
+
+        let inquiry = Scsi::device_inquiry(ctx, &device_path, generic_device).ok();
+
+        Some(Disk {
+            enclosure: enclosure_slot.to_string(),
+            slot,
+            temperature: get_disk_temperature(ctx, &device_path),
+            device_map: get_disk_block_device(ctx, &sg_name),
+            device_path,
+            vendor: inquiry.as_ref().map(|i| i.vendor.clone()).unwrap_or_default(),
+            model: inquiry.as_ref().map(|i| i.product.clone()).unwrap_or_default(),
+            serial: inquiry.as_ref().and_then(|i| i.serial.clone()),
+            fw_revision: inquiry.as_ref().map(|i| i.revision.clone()).unwrap_or_default(),
+            wwn: inquiry.and_then(|i| i.wwn),
+            led_locate_path: component.and_then(|c| get_disk_led_path(c, "locate")),
+            led_fault_path: component.and_then(|c| get_disk_led_path(c, "fault")),
+            flags: None,
+        })
+    }
+
+    /// Returns the scsi device of every SAS end device, by SAS address.
+    ///
+    /// The end devices are read from /sys/class/sas_device, the scsi device
+    /// sits under the target of the end device, example:
+    /// end_device-1:0:5/device/target1:0:5/1:0:5:0
     ///
-    fn get_disk_led_fault_path(enclosure_slot: &str, disk_slot: &str) -> String {
-        let sys_class_enclosure: &str = "/sys/class/enclosure/";
+    /// # Arguments
+    ///
+    /// * `ctx` - the context holding the sysfs root
+    ///
+    fn get_sas_devices(ctx: &Context) -> HashMap<u64, PathBuf> {
+        let mut devices = HashMap::new();
+        let Ok(end_devices) = fs::read_dir(ctx.sys_class("sas_device")) else {
+            return devices;
+        };
 
-        Util::verify_sysclass_folder(sys_class_enclosure);
+        for end_device in end_devices.filter_map(Result::ok).map(|e| e.path()) {
+            let address = fs::read_to_string(end_device.join("sas_address"))
+                .ok()
+                .and_then(|a| u64::from_str_radix(a.trim().trim_start_matches("0x"), 16).ok());
+            let Some(address) = address else {
+                continue;
+            };
+            let Ok(targets) = fs::read_dir(end_device.join("device")) else {
+                continue;
+            };
+            let device = targets
+                .filter_map(Result::ok)
+                .filter(|t| t.file_name().to_string_lossy().starts_with("target"))
+                .filter_map(|t| fs::read_dir(t.path()).ok())
+                .flat_map(|luns| luns.filter_map(Result::ok).map(|l| l.path()))
+                .find(|lun| lun.join("scsi_generic").is_dir());
+            if let Some(device) = device {
+                devices.insert(address, device);
+            }
+        }
 
-        let led_fault_path = sys_class_enclosure.to_string()
-            + enclosure_slot
-            + &"/".to_string()
-            + &disk_slot.to_string()
-            + &"/fault".to_string();
+        devices
+    }
 
-        if Util::path_exists(&led_fault_path) {
-            return led_fault_path;
-        } else {
-            return "NONE".to_string();
+    /// Returns the disks of an enclosure found from the SAS address of its
+    /// slots, skipping the slots already in `known`.
+    ///
+    /// This is the fallback used when the `ses` driver did not link a slot
+    /// to its disk in sysfs, example: the driver could not bind the
+    /// enclosure or a SATA disk sits behind an interposer. The SAS addresses
+    /// come from the additional element status page and are matched with
+    /// /sys/class/sas_device. Enclosures without that page return no disk.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to probe the disks
+    /// * `enclosure` - the enclosure we want to scan for disks
+    /// * `statuses` - the additional element status of the enclosure
+    /// * `components` - the sysfs paths of the enclosure components, if any
+    /// * `known` - the disks already found through sysfs
+    ///
+    fn get_disks_by_sas_address(
+        ctx: &Context,
+        enclosure: &BackPlane::Enclosure,
+        statuses: &[AdditionalStatus],
+        components: &[PathBuf],
+        known: &[Disk],
+    ) -> Vec<Disk> {
+        if statuses.is_empty() {
+            return Vec::new();
         }
+        let devices = get_sas_devices(ctx);
+
+        statuses
+            .iter()
+            .map(|status| (status.device_slot as u32, status))
+            .filter(|(slot, _)| !known.iter().any(|disk| disk.slot == *slot))
+            .filter_map(|(slot, status)| {
+                let device = status
+                    .sas_addresses
+                    .iter()
+                    .find_map(|address| devices.get(address))?;
+                let component = components
+                    .iter()
+                    .find(|c| is_slot_component(c) && get_disk_slot(c) == Some(slot));
+                probe_disk(ctx, device, &enclosure.slot, slot, component.map(PathBuf::as_path))
+            })
+            .collect()
     }
 
-    /// Here we write 0 or 1 into the disk led file
-    fn set_disk_led_locate(disk: String, option: &str) {
-        if Util::path_exists(&disk) {
-            let jbod = jbod_disk_map();
-            let found_disk: Vec<Disk> = jbod
-                .into_iter()
-                .filter(|v| (v.device_path == disk) || (v.device_map == disk))
-                .collect();
-            if !found_disk.is_empty() {
-                if Util::path_exists(&found_disk[0].led_locate_path) {
-                    fs::write(&found_disk[0].led_locate_path, option.clone())
-                        .expect("Unable to write on locate led");
-                    match option {
-                        "0" => {
-                            println!(
-                                "Disk slot: {} {}",
-                                found_disk[0].slot.green().bold(),
-                                option
-                            );
-                        }
-                        "1" => {
-                            println!(
-                                "Disk slot: {} {}",
-                                found_disk[0].slot.yellow().blink().bold(),
-                                option
-                            );
-                        }
-                        _ => println!("Option not identified"),
+    /// Returns the result of `get_disk_details` for every component, in the
+    /// same order as `components`.
+    ///
+    /// Every disk is probed with a few SCSI commands, on a 102 bay enclosure
+    /// doing it one after the other takes a while, so up to `ctx.concurrency`
+    /// workers pick the next component until there is none left.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to probe the disks
+    /// * `components` - the sysfs paths of the enclosure components
+    /// * `enclosure_slot` - the enclosure slot number, example: 15:0:0:0
+    ///
+    fn probe_components(
+        ctx: &Context,
+        components: &[PathBuf],
+        enclosure_slot: &str,
+    ) -> Vec<Option<Disk>> {
+        let next = AtomicUsize::new(0);
+        let results: Mutex<Vec<Option<Disk>>> = Mutex::new(vec![None; components.len()]);
+        let workers = ctx.concurrency.clamp(1, components.len().max(1));
+
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(component) = components.get(index) else {
+                        break;
+                    };
+                    let disk = get_disk_details(ctx, component, enclosure_slot);
+                    if let Ok(mut results) = results.lock() {
+                        results[index] = disk;
                     }
-                } else {
-                    println!(
-                        "{}: {} does not expose locate led",
-                        "Error".red().bold(),
-                        disk.yellow().bold()
-                    );
-                }
+                });
             }
-        } else {
-            println!(
-                "{} device {} not found",
-                "Error:".red().bold(),
-                disk.yellow().bold(),
-            );
-            exit(1);
-        }
+        });
+
+        results.into_inner().unwrap_or_default()
     }
 
-    /// Here we write 0 or 1 into the disk led file
-    fn set_disk_led_fault(disk: String, option: &str) {
-        if Util::path_exists(&disk) {
-            let jbod = jbod_disk_map();
-            let found_disk: Vec<Disk> = jbod
-                .into_iter()
-                .filter(|v| (v.device_path == disk) || (v.device_map == disk))
-                .collect();
-            if !found_disk.is_empty() {
-                if Util::path_exists(&found_disk[0].led_fault_path) {
-                    fs::write(&found_disk[0].led_fault_path, option.clone())
-                        .expect("Unable to write on locate led");
-                    match option {
-                        "0" => {
-                            println!(
-                                "Disk slot: {} {}",
-                                found_disk[0].slot.green().bold(),
-                                option
-                            );
-                        }
-                        "1" => {
-                            println!(
-                                "Disk slot: {} {}",
-                                found_disk[0].slot.red().blink().bold(),
-                                option
-                            );
-                        }
-                        _ => println!("Option not identified"),
-                    }
-                } else {
-                    println!(
-                        "{}: {} does not expose fault led",
-                        "Error".red().bold(),
-                        disk.yellow().bold()
-                    );
+    /// Returns a vector with the disks of one enclosure, sorted by slot
+    ///
+    /// The disks are found through the sysfs links of the `ses` driver, the
+    /// slots it did not link are matched by SAS address instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to probe the disks
+    /// * `enclosure` - the enclosure we want to scan for disks
+    ///
+    pub fn get_disks_per_enclosure(
+        ctx: &Context,
+        enclosure: &BackPlane::Enclosure,
+    ) -> Result<Vec<Disk>, JbodError> {
+        find_disks(ctx, enclosure, None)
+    }
+
+    /// Returns a vector with the disks of one enclosure, sorted by slot,
+    /// see `get_disks_per_enclosure`.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to probe the disks
+    /// * `enclosure` - the enclosure we want to scan for disks
+    /// * `additional` - the additional element status of the enclosure if
+    ///   it was already read, it is read only when a slot is not linked
+    ///
+    fn find_disks(
+        ctx: &Context,
+        enclosure: &BackPlane::Enclosure,
+        additional: Option<&[AdditionalStatus]>,
+    ) -> Result<Vec<Disk>, JbodError> {
+        let sas_disks = |components: &[PathBuf], known: &[Disk]| match additional {
+            Some(statuses) => get_disks_by_sas_address(ctx, enclosure, statuses, components, known),
+            None => {
+                let statuses = Ses::read_additional_element_status(ctx, &enclosure.device_path)
+                    .unwrap_or_default();
+                get_disks_by_sas_address(ctx, enclosure, &statuses, components, known)
+            }
+        };
+        let sys_class_enclosure = ctx.sys_class("enclosure");
+        let enclosure_path = sys_class_enclosure.join(&enclosure.slot);
+        let paths = match fs::read_dir(&enclosure_path) {
+            Ok(paths) => paths,
+            Err(err) => {
+                // The ses driver did not bind the enclosure.
+                let mut disks = sas_disks(&[], &[]);
+                if disks.is_empty() {
+                    Util::verify_sysclass_folder(&sys_class_enclosure)?;
+                    return Err(JbodError::from_io(enclosure_path.to_string_lossy(), err));
                 }
+                disks.sort_by_key(|d| d.slot);
+                return Ok(disks);
             }
-        } else {
-            println!(
-                "{} device {} not found",
-                "Error:".red().bold(),
-                disk.yellow().bold(),
-            );
-            exit(1);
+        };
+        let components: Vec<PathBuf> = paths.filter_map(Result::ok).map(|p| p.path()).collect();
+
+        let mut disks: Vec<Disk> = probe_components(ctx, &components, &enclosure.slot)
+            .into_iter()
+            .flatten()
+            .collect();
+        if components
+            .iter()
+            .any(|c| is_slot_component(c) && !c.join("device").exists())
+        {
+            let found = sas_disks(&components, &disks);
+            disks.extend(found);
         }
+        disks.sort_by_key(|d| d.slot);
+
+        Ok(disks)
     }
 
-    /// Returns strings with enclouse, slot, device path, temperature and the location of
-    /// the led's files
+    /// Fills the slots of an enclosure and copies the state bits of every
+    /// slot element to the disk plugged in it.
     ///
-    /// This function gets all details of a disk.
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to probe the enclosure
+    /// * `enclosure` - the enclosure to fill, with its disks
+    ///
+    pub fn get_enclosure_slots(
+        ctx: &Context,
+        enclosure: &mut BackPlane::Enclosure,
+    ) -> Result<(), JbodError> {
+        let pages = Ses::read_enclosure_pages(ctx, &enclosure.device_path)
+            .map_err(|err| JbodError::from_io(&enclosure.device_path, err))?;
+        BackPlane::fill_enclosure_slots(enclosure, &pages);
+
+        Ok(())
+    }
+
+    /// Returns the disk holding a block device, example: sdb for sdb1, or
+    /// the block device itself if it is not a partition.
     ///
     /// # Arguments
     ///
-    /// * `device` - a string with the device path
-    /// * `enclosure_slot` - the enclosure slot number, example: 15:0:0:0
+    /// * `ctx` - the context holding the sysfs root
+    /// * `name` - the block device name, example: sdb1
     ///
-    fn get_disk_details(
-        device: String,
-        enclosure_slot: String,
-    ) -> (
-        String,
-        String,
-        String,
-        String,
-        String,
-        String,
-        String,
-        String,
-        String,
-        String,
-    ) {
-        let sys_class_enclosure: &str = "/sys/class/enclosure/";
-        let mut enclosure = String::new();
-        let mut slot = String::new();
-        let mut device_path = String::new();
-        let mut temperature = String::new();
-        let mut fw_revision = String::new();
-        let mut vendor = String::new();
-        let mut model = String::new();
-        let mut serial = String::new();
-        let mut disk_locate_led = String::new();
-        let mut disk_fault_led = String::new();
-
-        let path_tostr_spl: Vec<&str> = device.split('/').collect();
-        let _slot = path_tostr_spl[5];
-        let cmp_slot = _slot.to_lowercase();
-
-        Util::verify_sysclass_folder(sys_class_enclosure);
-
-        if cmp_slot.contains("slot")
-            || cmp_slot.contains("disk")
-            || cmp_slot.contains("array device")
-            || cmp_slot.bytes().all(|c| c.is_ascii_digit())
-        {
-            let generic_device = format!("{sys_class_enclosure}{enclosure_slot}/{_slot}/device");
-            let physical_device = format!("{generic_device}/scsi_generic/");
-
-            if Util::path_exists(&physical_device) {
-                let physical_path = fs::read_dir(physical_device).unwrap();
-                for dev in physical_path {
-                    let _dev = dev.unwrap().path();
-                    let __dev = _dev.to_str().unwrap();
-                    let split_dev: Vec<&str> = __dev.split('/').collect();
-                    let __get_slot: Vec<&str> = split_dev[5].split(',').collect();
-                    enclosure = split_dev[4].to_string();
-                    slot = __get_slot[0].to_string();
-                    device_path = format!("/dev/{}", split_dev[8]);
-                    temperature = get_disk_temperature(device_path.clone());
-                    fw_revision = get_disk_firmware(device_path.clone());
-                    vendor = get_disk_vendor(generic_device.clone().to_string());
-                    model = get_disk_model(generic_device.clone().to_string());
-                    serial = get_disk_serial(generic_device.clone().to_string());
-                    disk_locate_led = get_disk_led_locate_path(&enclosure_slot, split_dev[5]);
-                    disk_fault_led = get_disk_led_fault_path(&enclosure_slot, split_dev[5]);
-                }
-            }
+    fn get_parent_block_device(ctx: &Context, name: &str) -> String {
+        let class = ctx.sys_class("block");
+        if !class.join(name).join("partition").exists() {
+            return name.to_string();
         }
-        (
-            enclosure,
-            slot,
-            device_path,
-            temperature,
-            fw_revision,
-            vendor,
-            model,
-            serial,
-            disk_locate_led,
-            disk_fault_led,
-        )
+
+        // The partition is a directory of its disk, /sys/class/block/sdb/sdb1,
+        // look for it instead of following the link of /sys/class/block/sdb1:
+        // a captured tree may have been copied without its links.
+        fs::read_dir(&class)
+            .into_iter()
+            .flatten()
+            .filter_map(Result::ok)
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .find(|disk| disk != name && class.join(disk).join(name).join("partition").exists())
+            .unwrap_or_else(|| name.to_string())
+    }
+
+    /// Returns true if a block device or one of its partitions is held by
+    /// another block device, example: an md array, a multipath map or a dm
+    /// device, or `None` if the block device is not in sysfs.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context holding the sysfs root
+    /// * `name` - the block device name, example: sdb
+    ///
+    fn is_held(ctx: &Context, name: &str) -> Option<bool> {
+        let block = ctx.sys_class("block").join(name);
+        let has_holders = |path: &Path| {
+            fs::read_dir(path.join("holders"))
+                .map(|mut holders| holders.next().is_some())
+                .unwrap_or(false)
+        };
+        let mut partitions = fs::read_dir(&block)
+            .ok()?
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with(name));
+
+        Some(has_holders(&block) || partitions.any(|entry| has_holders(&entry.path())))
+    }
+
+    /// The array bits of the slots of one enclosure, see `get_raid_slot_flags`.
+    #[derive(Debug, Clone, Default)]
+    pub struct RaidSlotFlags {
+        /// The slot number and the array bits of the slots to sync.
+        pub requested: Vec<(u32, SlotStatus)>,
+        /// The slot number and the block device of the disks held by
+        /// something else than an md array, example: a multipath map.
+        pub skipped: Vec<(u32, String)>,
     }
 
-    /// Returns a vector of disk structure
+    /// Returns the array bits every slot should report from the state of
+    /// the md arrays, the disks are matched through their `device_map`.
     ///
-    /// This function collects all information of a disk
+    /// * hot spare - the disk is a spare of an array
+    /// * in failed array - the disk is in an array that lost too many devices
+    /// * in critical array - the disk is in a degraded array
+    /// * rebuild/remap - the disk is being rebuilt
+    ///
+    /// A disk with partitions in several arrays gets the bits of all of them.
+    /// A disk in no md array gets no bit, unless it is held by another block
+    /// device: the array it belongs to is unknown, so its slot is skipped.
+    /// The slots without a block device are left out as well.
     ///
     /// # Arguments
     ///
-    /// * `enc_vec` - A vector including all enclosures we want to scan for disks.
-    ///
-    fn get_disks_per_enclosure(enc_vec: Vec<BackPlane::Enclosure>) -> Vec<Disk> {
-        let mut disk: Vec<Disk> = Vec::new();
-        let sys_class_enclosure: &str = "/sys/class/enclosure/";
-        let sg_map = get_disk_sd_map(); // Get all sg_map once in a HashMap
-
-        Util::verify_sysclass_folder(sys_class_enclosure);
-
-        for enclosure in enc_vec {
-            let paths = fs::read_dir(sys_class_enclosure.to_string() + &enclosure.slot).unwrap();
-            for path in paths {
-                let _get_path = path.unwrap().path();
-
-                let path_tostr = _get_path.to_str().unwrap();
-                let (
-                    _enclosure,
-                    _slot,
-                    _device_path,
-                    _temperature,
-                    _fw_revision,
-                    _vendor,
-                    _model,
-                    _serial,
-                    _led_locate_path,
-                    _led_fault_path,
-                ) = get_disk_details(path_tostr.to_string(), enclosure.slot.to_string());
-
-                if !_device_path.is_empty() {
-                    disk.push(Disk {
-                        enclosure: _enclosure,
-                        slot: _slot,
-                        device_map: sg_map.get(&_device_path).unwrap().to_string(),
-                        device_path: _device_path,
-                        temperature: _temperature,
-                        fw_revision: _fw_revision,
-                        vendor: _vendor,
-                        model: _model,
-                        serial: _serial,
-                        led_locate_path: _led_locate_path,
-                        led_fault_path: _led_fault_path,
-                    });
+    /// * `ctx` - the context holding the sysfs root
+    /// * `disks` - the disks of one enclosure
+    /// * `arrays` - the md arrays, example: the output of `MdStat::read`
+    ///
+    pub fn get_raid_slot_flags(ctx: &Context, disks: &[Disk], arrays: &[MdArray]) -> RaidSlotFlags {
+        let members: Vec<(String, &MdArray, &MdMember)> = arrays
+            .iter()
+            .flat_map(|array| array.members.iter().map(move |member| (array, member)))
+            .map(|(array, member)| (get_parent_block_device(ctx, &member.name), array, member))
+            .collect();
+
+        let mut flags = RaidSlotFlags::default();
+        for disk in disks {
+            let Some(device_map) = disk.device_map.as_deref() else {
+                continue;
+            };
+            let name = device_map.trim_start_matches("/dev/");
+            let mut bits = SlotStatus::default();
+            let mut member_of = members.iter().filter(|(parent, _, _)| parent == name).peekable();
+            if member_of.peek().is_none() && is_held(ctx, name) != Some(false) {
+                flags.skipped.push((disk.slot, device_map.to_string()));
+                continue;
+            }
+            for (_, array, member) in member_of {
+                if member.spare {
+                    bits.hot_spare = true;
+                    continue;
                 }
+                bits.in_failed_array |= array.failed();
+                bits.in_critical_array |= array.degraded();
+                bits.rebuild_remap |= array.rebuilding(member);
             }
+            flags.requested.push((disk.slot, bits));
         }
 
-        disk
+        flags
     }
 
     /// Returns a vector with disk structure
     ///
     /// This is the public function that returns all disks and its information.
     ///
-    pub fn jbod_disk_map() -> Vec<Disk> {
-        let enc = BackPlane::get_enclosure();
-        let disks = get_disks_per_enclosure(enc);
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to probe the enclosures and disks
+    ///
+    pub fn jbod_disk_map(ctx: &Context) -> Result<Vec<Disk>, JbodError> {
+        let mut disks = Vec::new();
+        for enclosure in BackPlane::get_enclosure(ctx)?.iter() {
+            disks.extend(get_disks_per_enclosure(ctx, enclosure)?);
+        }
 
-        disks
+        Ok(disks)
     }
 
-    /// [TODO] fix the return
+    /// Returns a vector with the enclosures owning their disks, slots, FAN,
+    /// power supplies and sensors
     ///
-    /// This function handles the disk led manipulation
+    /// Every enclosure is collected on its own: the enclosure pages are read
+    /// once and the slots and the other elements come from them. An
+    /// enclosure that does not answer them, example: a sysfs tree captured
+    /// from another machine, keeps its disks, and an enclosure whose disks
+    /// cannot be listed keeps its elements. The failures are recorded in
+    /// its `errors`.
     ///
     /// # Arguments
     ///
-    /// * `options` - a reference of ArgMatches
-    ///
-    pub fn jbod_led_switch(options: &ArgMatches) -> Result<(), ()> {
-        let is_locate = options.is_present("locate");
-        let is_fault = options.is_present("fault");
-        let on = options.is_present("on");
-        let off = options.is_present("off");
-
-        if on && off {
-            println!(
-                "Not christmas yet {}{}{}!",
-                ":".green().bold(),
-                "_".yellow().bold().blink(),
-                ")".red().bold()
-            );
-            exit(1);
-        }
-
-        if is_locate {
-            let disk = options
-                .value_of("locate")
-                .unwrap_or(&"/dev/null".to_string())
-                .to_string();
-            if on {
-                set_disk_led_locate(disk.clone(), &"1".to_string());
+    /// * `ctx` - the context used to probe the enclosures and disks
+    ///
+    pub fn jbod_inventory(ctx: &Context) -> Result<Vec<BackPlane::Enclosure>, JbodError> {
+        let mut enclosures = BackPlane::get_enclosure(ctx)?;
+        for enclosure in enclosures.iter_mut() {
+            let pages = Ses::read_enclosure_pages(ctx, &enclosure.device_path)
+                .map_err(|err| JbodError::from_io(&enclosure.device_path, err));
+            let additional = pages.as_ref().ok().map(|p| p.additional.as_slice());
+            match find_disks(ctx, enclosure, additional) {
+                Ok(disks) => enclosure.disks = disks,
+                Err(err) => enclosure.errors.push(err.to_string()),
             }
-            if off {
-                set_disk_led_locate(disk.clone(), &"0".to_string());
-            }
-        }
-
-        if is_fault {
-            let disk = options
-                .value_of("fault")
-                .unwrap_or(&"/dev/null".to_string())
-                .to_string();
-            if on {
-                set_disk_led_fault(disk.clone(), &"1".to_string());
-            }
-            if off {
-                set_disk_led_fault(disk.clone(), &"0".to_string());
+            match pages {
+                Ok(pages) => {
+                    BackPlane::fill_enclosure_slots(enclosure, &pages);
+                    BackPlane::fill_enclosure_elements(enclosure, &pages.status);
+                }
+                Err(err) => enclosure.errors.push(err.to_string()),
             }
         }
 
-        Ok(())
+        Ok(enclosures)
     }
 }
@@ -30,262 +30,991 @@
 
 #[allow(non_snake_case)]
 pub mod BackPlane {
+    use serde::Serialize;
     use std::fmt;
-    use std::io::{BufRead, BufReader};
-    use std::process::{Command};
-    use regex::Regex;
+    use std::io;
+    use std::path::Path;
+    use std::thread;
+    use std::time::Duration;
 
-    use crate::utils::helper::Util::{LSSCSI, SG_INQ, SG_SES};
+    use crate::jbod::disks::DiskShelf::Disk;
+    use crate::jbod::scsi::Scsi;
+    use crate::jbod::ses::Ses;
+    use crate::jbod::ses::Ses::{
+        EnclosurePages,
+        AdditionalStatus, AlarmStatus, CoolingStatus, ElementStatusCode, ElementType,
+        EnclosureStatus, PowerSupplyStatus, SensorStatus, SlotStatus,
+    };
+    use crate::utils::error::Error::JbodError;
+    use crate::utils::helper::Util::{CONTROL_VERIFY_RETRIES, LSSCSI, SYSFS_ROOT};
+    use crate::utils::runner::Runner::Context;
 
     extern crate prettytable;
-    extern crate subprocess;
     use prettytable::{color, format, Attr, Cell, Row, Table};
 
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Default, Serialize)]
     pub struct Enclosure {
+        /// The enclosure SCSI address, example: 15:0:1:0
         pub slot: String,
+        /// The scsi generic device, example: /dev/sg9
         pub device_path: String,
         pub vendor: String,
         pub model: String,
         pub revision: String,
-        pub serial: String,
+        /// The unit serial number, `None` if the enclosure does not report it.
+        pub serial: Option<String>,
+        /// The enclosure NAA identifier, example: 0x5000cca05a7b1c3e
+        pub wwn: Option<String>,
+        /// The disks behind the enclosure, filled by `DiskShelf::jbod_inventory`.
+        pub disks: Vec<Disk>,
+        /// The FAN of the enclosure, filled by `DiskShelf::jbod_inventory`.
+        pub fans: Vec<EnclosureFan>,
+        /// The power supplies of the enclosure, filled by `DiskShelf::jbod_inventory`.
+        pub psus: Vec<EnclosurePsu>,
+        /// The sensors of the enclosure, filled by `DiskShelf::jbod_inventory`.
+        pub sensors: Vec<EnclosureSensor>,
+        /// Every disk slot of the enclosure, empty or not, filled by
+        /// `DiskShelf::jbod_inventory`.
+        pub slots: Vec<EnclosureSlot>,
+        /// The enclosure pages `DiskShelf::jbod_inventory` could not read,
+        /// the disks are still listed without them.
+        pub errors: Vec<String>,
     }
 
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Serialize)]
     pub struct EnclosureFan {
-        /// The slot number provided by the JBOD
+        /// The SCSI address of the enclosure holding the fan, example: 15:0:1:0
         pub slot: String,
-        /// The device serial number
-        pub serial: String,
-        /// The name of the component provided by the JBOD.
+        /// The serial number of that enclosure, the fan has none of its own.
+        pub serial: Option<String>,
+        /// The element descriptor text, example: "Fan 1".
         pub description: String,
-        /// The slot position used by `sg_ses`.
+        /// The element index as printed by `sg_ses`, example: 3,0
         pub index: String,
+        /// The element status, example: OK or Critical.
+        pub status: ElementStatusCode,
         /// The RPM speed of the FAN.
         pub speed: i64,
-        /// The JBOD can provide extra information about the FAN speed
-        /// and it can be used to create alerts in the future.
-        pub comment: String,
-    }
-
-    /// Creates the pretty table for the enclosure.
-    fn create_enclosure_table() -> Table {
-        let mut enclosure_table = Table::new();
-        enclosure_table.set_format(*format::consts::FORMAT_NO_BORDER);
-        enclosure_table.add_row(Row::new(vec![
-            Cell::new("SLOT")
-                .with_style(Attr::Bold)
-                .with_style(Attr::ForegroundColor(color::BLUE)),
-            Cell::new("DEVICE")
-                .with_style(Attr::Bold)
-                .with_style(Attr::ForegroundColor(color::BLUE)),
-            Cell::new("VENDOR")
-                .with_style(Attr::Bold)
-                .with_style(Attr::ForegroundColor(color::BLUE)),
-            Cell::new("MODEL")
-                .with_style(Attr::Bold)
-                .with_style(Attr::ForegroundColor(color::BLUE)),
-            Cell::new("REVISION")
-                .with_style(Attr::Bold)
-                .with_style(Attr::ForegroundColor(color::BLUE)),
-            Cell::new("SERIAL")
-                .with_style(Attr::Bold)
-                .with_style(Attr::ForegroundColor(color::BLUE)),
-        ]));
-
-        enclosure_table
-    }
-
-    /// Creates the pretty table for the FAN.
-    pub fn create_fan_table() -> Table {
-        let mut enclosure_table = Table::new();
-        enclosure_table.set_format(*format::consts::FORMAT_NO_BORDER);
-        enclosure_table.add_row(Row::new(vec![
-            Cell::new("SLOT")
-                .with_style(Attr::Bold)
-                .with_style(Attr::ForegroundColor(color::BLUE)),
-            Cell::new("IDENT")
-                .with_style(Attr::Bold)
-                .with_style(Attr::ForegroundColor(color::BLUE)),
-            Cell::new("DESCRIPTION")
-                .with_style(Attr::Bold)
-                .with_style(Attr::ForegroundColor(color::BLUE)),
-            Cell::new("STATUS")
-                .with_style(Attr::Bold)
-                .with_style(Attr::ForegroundColor(color::BLUE)),
-            Cell::new("RPM")
-                .with_style(Attr::Bold)
-                .with_style(Attr::ForegroundColor(color::BLUE)),
-        ]));
-
-        enclosure_table
+        /// The actual speed code, from 0 (stopped) to 7 (highest speed).
+        pub speed_code: u8,
+        /// The FAN failure and requested on/off bits.
+        pub flags: CoolingStatus,
+    }
+
+    #[derive(Debug, Clone, Serialize)]
+    pub struct EnclosurePsu {
+        /// The SCSI address of the enclosure holding the power supply, example: 15:0:1:0
+        pub slot: String,
+        /// The serial number of that enclosure, the power supply has none of its own.
+        pub serial: Option<String>,
+        /// The element descriptor text, example: "Power Supply A".
+        pub description: String,
+        /// The element index as printed by `sg_ses`, example: 2,0
+        pub index: String,
+        /// The element status, example: OK or Critical.
+        pub status: ElementStatusCode,
+        /// The power supply failure and state bits.
+        pub flags: PowerSupplyStatus,
+    }
+
+    #[derive(Debug, Clone, Serialize)]
+    pub struct EnclosureAlarm {
+        /// The SCSI address of the enclosure holding the alarm, example: 15:0:1:0
+        pub slot: String,
+        /// The serial number of that enclosure, the alarm has none of its own.
+        pub serial: Option<String>,
+        /// The element descriptor text, example: "Audible Alarm".
+        pub description: String,
+        /// The element index as printed by `sg_ses`, example: 6,0
+        pub index: String,
+        /// The element status, example: OK or Critical.
+        pub status: ElementStatusCode,
+        /// The mute, remind and tone urgency bits.
+        pub flags: AlarmStatus,
+    }
+
+    /// What sits in a disk slot, from the enclosure and the OS point of view.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub enum BayState {
+        /// The enclosure reports no device in the slot.
+        Empty,
+        /// A device is in the slot and the OS sees its disk.
+        PresentOk,
+        /// The enclosure reports a failure of the device in the slot.
+        PresentFailed,
+        /// The enclosure reports a device in the slot but the OS does not see it.
+        NotResponding,
+        /// The enclosure does not report the status of the slot.
+        Unsupported,
+    }
+
+    impl fmt::Display for BayState {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let state = match self {
+                BayState::Empty => "EMPTY",
+                BayState::PresentOk => "PRESENT-OK",
+                BayState::PresentFailed => "PRESENT-FAILED",
+                BayState::NotResponding => "NOT-RESPONDING",
+                BayState::Unsupported => "UNSUPPORTED",
+            };
+            write!(f, "{}", state)
+        }
+    }
+
+    /// Returns the state of a slot from the status code of its element and
+    /// whether the OS sees a disk in it.
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::BackPlane::{bay_state, BayState};
+    /// use jbod::Ses::ElementStatusCode;
+    ///
+    /// assert_eq!(bay_state(ElementStatusCode::NotInstalled, false), BayState::Empty);
+    /// assert_eq!(bay_state(ElementStatusCode::Ok, true), BayState::PresentOk);
+    /// assert_eq!(bay_state(ElementStatusCode::Ok, false), BayState::NotResponding);
+    /// assert_eq!(bay_state(ElementStatusCode::Critical, true), BayState::PresentFailed);
+    /// ```
+    pub fn bay_state(status: ElementStatusCode, disk_present: bool) -> BayState {
+        match status {
+            ElementStatusCode::Unsupported => BayState::Unsupported,
+            ElementStatusCode::NotInstalled => BayState::Empty,
+            ElementStatusCode::Critical
+            | ElementStatusCode::NonCritical
+            | ElementStatusCode::Unrecoverable => BayState::PresentFailed,
+            _ if disk_present => BayState::PresentOk,
+            _ => BayState::NotResponding,
+        }
+    }
+
+    #[derive(Debug, Clone, Serialize)]
+    pub struct EnclosureSlot {
+        /// The SCSI address of the enclosure holding the slot, example: 15:0:1:0
+        pub slot: String,
+        /// The serial number of that enclosure, not of the disk in the slot.
+        pub serial: Option<String>,
+        /// The element descriptor text, example: "Slot 03".
+        pub description: String,
+        /// The element index as printed by `sg_ses`, example: 1,17
+        pub index: String,
+        /// The slot number, the same as `Disk::slot`.
+        pub number: u32,
+        /// The element status, example: OK or Not Installed.
+        pub status: ElementStatusCode,
+        /// What sits in the slot, from the enclosure and the OS point of view.
+        pub state: BayState,
+        /// The swap, predicted failure, hot spare, rebuild, ... bits.
+        pub flags: SlotStatus,
+    }
+
+    /// An array device slot whose array bits do not match the requested ones.
+    #[derive(Debug, Clone)]
+    pub struct SlotChange {
+        /// The slot as reported before the change.
+        pub slot: EnclosureSlot,
+        /// The block device of the disk in the slot, example: /dev/sdb
+        pub device_map: Option<String>,
+        /// The requested hot spare, in critical array, in failed array and
+        /// rebuild/remap bits, the other bits are not used.
+        pub requested: SlotStatus,
+    }
+
+    impl SlotChange {
+        /// Returns the array bits turned on and off by the change, example:
+        /// (["rebuild_remap"], ["in_critical_array"]).
+        pub fn diff(&self) -> (Vec<&'static str>, Vec<&'static str>) {
+            let mut on = Vec::new();
+            let mut off = Vec::new();
+            for (name, current, requested) in array_bits(&self.slot.flags, &self.requested) {
+                if requested && !current {
+                    on.push(name);
+                } else if current && !requested {
+                    off.push(name);
+                }
+            }
+            (on, off)
+        }
+    }
+
+    /// Returns the name, current and requested value of the array bits set
+    /// from the RAID state.
+    fn array_bits(current: &SlotStatus, requested: &SlotStatus) -> [(&'static str, bool, bool); 4] {
+        [
+            ("hot_spare", current.hot_spare, requested.hot_spare),
+            ("in_critical_array", current.in_critical_array, requested.in_critical_array),
+            ("in_failed_array", current.in_failed_array, requested.in_failed_array),
+            ("rebuild_remap", current.rebuild_remap, requested.rebuild_remap),
+        ]
+    }
+
+    /// What an enclosure sensor measures.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum SensorKind {
+        Temperature,
+        Voltage,
+        Current,
+    }
+
+    impl SensorKind {
+        /// Returns the unit of the sensor value, example: "C" for Celsius.
+        pub fn unit(&self) -> &'static str {
+            match self {
+                SensorKind::Temperature => "C",
+                SensorKind::Voltage => "V",
+                SensorKind::Current => "A",
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Serialize)]
+    pub struct EnclosureSensor {
+        /// The SCSI address of the enclosure holding the sensor, example: 15:0:1:0
+        pub slot: String,
+        /// The serial number of that enclosure, the sensor has none of its own.
+        pub serial: Option<String>,
+        /// The element descriptor text, example: "Temp Sensor 0".
+        pub description: String,
+        /// The element index as printed by `sg_ses`, example: 4,0
+        pub index: String,
+        /// The sensor type, it gives the unit of `value`.
+        pub kind: SensorKind,
+        /// The element status, example: OK or Critical.
+        pub status: ElementStatusCode,
+        /// The reading in the unit of `kind`, `None` if the sensor has none.
+        pub value: Option<f64>,
+        /// The sensor failure and threshold bits.
+        pub flags: SensorStatus,
+    }
+
+    /// Creates a borderless pretty table with a bold blue header row.
+    ///
+    /// # Arguments
+    ///
+    /// * `header` - The column titles
+    /// * `rows` - The table rows, in the order they are printed
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use jbod::BackPlane;
+    /// use prettytable::{Cell, Row};
+    ///
+    /// let table = BackPlane::create_table(
+    ///     &["SLOT", "STATUS"],
+    ///     vec![Row::new(vec![Cell::new("15:0:1:0"), Cell::new("OK")])],
+    /// );
+    /// assert_eq!(table.len(), 2);
+    /// ```
+    pub fn create_table(header: &[&str], rows: Vec<Row>) -> Table {
+        let mut table = Table::new();
+        table.set_format(*format::consts::FORMAT_NO_BORDER);
+        table.add_row(Row::new(
+            header
+                .iter()
+                .map(|title| {
+                    Cell::new(title)
+                        .with_style(Attr::Bold)
+                        .with_style(Attr::ForegroundColor(color::BLUE))
+                })
+                .collect(),
+        ));
+        for row in rows {
+            table.add_row(row);
+        }
+
+        table
     }
 
     /// Implementation to print the enclosure table without deal with the table.
     impl fmt::Display for Enclosure {
-        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-            let mut enclosure_table = create_enclosure_table();
-            enclosure_table.add_row(Row::new(vec![
-                Cell::new(&self.slot),
-                Cell::new(&self.device_path),
-                Cell::new(&self.vendor),
-                Cell::new(&self.model),
-                Cell::new(&self.revision),
-                Cell::new(&self.serial),
-            ]));
+        fn fmt(&self, _f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let enclosure_table = create_table(
+                &["SLOT", "DEVICE", "VENDOR", "MODEL", "REVISION", "SERIAL"],
+                vec![Row::new(vec![
+                    Cell::new(&self.slot),
+                    Cell::new(&self.device_path),
+                    Cell::new(&self.vendor),
+                    Cell::new(&self.model),
+                    Cell::new(&self.revision),
+                    Cell::new(self.serial.as_deref().unwrap_or("NONE")),
+                ])],
+            );
 
             enclosure_table.printstd();
             Ok(())
         }
     }
 
-    /// Returns strings with vendor, ident, rev and serial of the enclosure.
-    ///
-    /// This function get information from a given enclosure.
+    /// Returns the INQUIRY details of a given enclosure, or `None` if
+    /// neither the enclosure nor sysfs has them.
     ///
     /// # Arguments
     ///
+    /// * `ctx` - the context used to send INQUIRY
     /// * `device` - a string with the device path of the enclosure
+    /// * `sysfs_device` - the sysfs directory of the enclosure scsi device
     ///
     /// # Example
-    /// ```
-    /// let (vendor, ident, rev, serial) = get_enclosure_details("/dev/sg9".to_string());
+    /// ```ignore
+    /// let inquiry = get_enclosure_details(&ctx, "/dev/sg9", Path::new("/sys/class/scsi_generic/sg9/device"));
     /// ```
     ///
-    fn get_enclosure_details(device: String) -> (String, String, String, String) {
-        let mut vendor = "NONE".to_string();
-        let mut ident = "NONE".to_string();
-        let mut rev = "NONE".to_string();
-        let mut serial = "NONE".to_string();
-        let sginq_cmd = Command::new(SG_INQ)
-            .args(&[device])
-            .output()
-            .expect("Failed to sg_inq the device");
-        let sginq_output = String::from_utf8_lossy(&sginq_cmd.stdout);
-
-        for output in sginq_output.split('\n') {
-            if output.contains("Vendor") {
-                vendor = output
-                    .replace("Vendor identification:", "")
-                    .trim()
-                    .to_string();
-            }
-            if output.contains("identification") {
-                ident = output
-                    .replace("Product identification:", "")
-                    .trim()
-                    .to_string();
+    fn get_enclosure_details(
+        ctx: &Context,
+        device: &str,
+        sysfs_device: &Path,
+    ) -> Option<Scsi::Inquiry> {
+        Scsi::device_inquiry(ctx, device, sysfs_device).ok()
+    }
+
+    /// Returns the enclosure status page of an enclosure.
+    fn read_status(ctx: &Context, enclosure: &Enclosure) -> Result<EnclosureStatus, JbodError> {
+        Ses::read_enclosure_status(ctx, &enclosure.device_path)
+            .map_err(|err| JbodError::from_io(&enclosure.device_path, err))
+    }
+
+    /// Returns the enclosure status and additional element status pages of
+    /// an enclosure.
+    fn read_pages(ctx: &Context, enclosure: &Enclosure) -> Result<EnclosurePages, JbodError> {
+        Ses::read_enclosure_pages(ctx, &enclosure.device_path)
+            .map_err(|err| JbodError::from_io(&enclosure.device_path, err))
+    }
+
+    /// Returns the EnclosureFan structure for each Cooling element of a
+    /// status page.
+    fn fans_from_status(enclosure: &Enclosure, status: &EnclosureStatus) -> Vec<EnclosureFan> {
+        status
+            .elements
+            .iter()
+            .filter(|e| e.element_type == ElementType::Cooling && e.index.is_some())
+            .map(|fan| EnclosureFan {
+                slot: enclosure.slot.clone(),
+                serial: enclosure.serial.clone(),
+                description: fan.descriptor.clone(),
+                index: fan.sg_ses_index(),
+                status: fan.status_code(),
+                speed: Ses::fan_speed(&fan.status),
+                speed_code: Ses::fan_speed_code(&fan.status),
+                flags: Ses::decode_cooling(&fan.status),
+            })
+            .collect()
+    }
+
+    /// Returns the EnclosurePsu structure for each Power Supply element of
+    /// a status page.
+    fn psus_from_status(enclosure: &Enclosure, status: &EnclosureStatus) -> Vec<EnclosurePsu> {
+        status
+            .elements
+            .iter()
+            .filter(|e| e.element_type == ElementType::PowerSupply && e.index.is_some())
+            .map(|psu| EnclosurePsu {
+                slot: enclosure.slot.clone(),
+                serial: enclosure.serial.clone(),
+                description: psu.descriptor.clone(),
+                index: psu.sg_ses_index(),
+                status: psu.status_code(),
+                flags: Ses::decode_power_supply(&psu.status),
+            })
+            .collect()
+    }
+
+    /// Returns the EnclosureAlarm structure for each Audible Alarm element
+    /// of a status page.
+    fn alarms_from_status(enclosure: &Enclosure, status: &EnclosureStatus) -> Vec<EnclosureAlarm> {
+        status
+            .elements
+            .iter()
+            .filter(|e| e.element_type == ElementType::AudibleAlarm && e.index.is_some())
+            .map(|alarm| EnclosureAlarm {
+                slot: enclosure.slot.clone(),
+                serial: enclosure.serial.clone(),
+                description: alarm.descriptor.clone(),
+                index: alarm.sg_ses_index(),
+                status: alarm.status_code(),
+                flags: Ses::decode_audible_alarm(&alarm.status),
+            })
+            .collect()
+    }
+
+    /// Returns the EnclosureSensor structure for each sensor element of a
+    /// status page.
+    fn sensors_from_status(enclosure: &Enclosure, status: &EnclosureStatus) -> Vec<EnclosureSensor> {
+        status
+            .elements
+            .iter()
+            .filter(|e| e.index.is_some())
+            .filter_map(|sensor| {
+                let (kind, value, flags) = match sensor.element_type {
+                    ElementType::TemperatureSensor => {
+                        let decoded = Ses::decode_temperature_sensor(&sensor.status);
+                        (
+                            SensorKind::Temperature,
+                            decoded.temperature.map(f64::from),
+                            decoded.flags,
+                        )
+                    }
+                    ElementType::VoltageSensor => {
+                        let decoded = Ses::decode_voltage_sensor(&sensor.status);
+                        (SensorKind::Voltage, Some(decoded.value), decoded.flags)
+                    }
+                    ElementType::CurrentSensor => {
+                        let decoded = Ses::decode_current_sensor(&sensor.status);
+                        (SensorKind::Current, Some(decoded.value), decoded.flags)
+                    }
+                    _ => return None,
+                };
+                Some(EnclosureSensor {
+                    slot: enclosure.slot.clone(),
+                    serial: enclosure.serial.clone(),
+                    description: sensor.descriptor.clone(),
+                    index: sensor.sg_ses_index(),
+                    kind,
+                    status: sensor.status_code(),
+                    value,
+                    flags,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the EnclosureSlot structure for each Device Slot and Array
+    /// Device Slot element of a status page.
+    ///
+    /// The slot number is the device slot number of the additional element
+    /// status page, like the `ses` driver does, or the position of the
+    /// element among the slots when the enclosure does not provide it.
+    fn slots_from_status(
+        enclosure: &Enclosure,
+        status: &EnclosureStatus,
+        additional: &[AdditionalStatus],
+    ) -> Vec<EnclosureSlot> {
+        status
+            .elements
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| {
+                matches!(e.element_type, ElementType::DeviceSlot | ElementType::ArrayDeviceSlot)
+                    && e.index.is_some()
+            })
+            .enumerate()
+            .map(|(position, (element, slot))| {
+                let number = additional
+                    .iter()
+                    .find(|a| a.element == element)
+                    .map_or(position as u32, |a| a.device_slot as u32);
+                let disk_present = enclosure.disks.iter().any(|disk| disk.slot == number);
+                EnclosureSlot {
+                    slot: enclosure.slot.clone(),
+                    serial: enclosure.serial.clone(),
+                    description: slot.descriptor.clone(),
+                    index: slot.sg_ses_index(),
+                    number,
+                    status: slot.status_code(),
+                    state: bay_state(slot.status_code(), disk_present),
+                    flags: Ses::decode_slot(&slot.status),
+                }
+            })
+            .collect()
+    }
+
+    /// Fills the FAN, power supplies and sensors of an enclosure, the
+    /// enclosure status page is read only once.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to probe the enclosure
+    /// * `enclosure` - the enclosure to fill
+    ///
+    pub fn get_enclosure_elements(ctx: &Context, enclosure: &mut Enclosure) -> Result<(), JbodError> {
+        let status = read_status(ctx, enclosure)?;
+        fill_enclosure_elements(enclosure, &status);
+
+        Ok(())
+    }
+
+    /// Fills the FAN, power supplies and sensors of an enclosure from a
+    /// status page already read.
+    ///
+    /// # Arguments
+    ///
+    /// * `enclosure` - the enclosure to fill
+    /// * `status` - the enclosure status page of the enclosure
+    ///
+    pub fn fill_enclosure_elements(enclosure: &mut Enclosure, status: &EnclosureStatus) {
+        enclosure.fans = fans_from_status(enclosure, status);
+        enclosure.psus = psus_from_status(enclosure, status);
+        enclosure.sensors = sensors_from_status(enclosure, status);
+    }
+
+    /// Returns a vector with the EnclosureFan structure for each FAN of
+    /// one enclosure.
+    ///
+    /// This function reads the enclosure status page and collects
+    /// information from each Cooling element.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to probe the enclosure
+    /// * `enclosure` - the enclosure to read the FAN from
+    ///
+    pub fn get_fans_per_enclosure(
+        ctx: &Context,
+        enclosure: &Enclosure,
+    ) -> Result<Vec<EnclosureFan>, JbodError> {
+        let status = read_status(ctx, enclosure)?;
+        Ok(fans_from_status(enclosure, &status))
+    }
+
+    /// Returns a vector with the EnclosurePsu structure for each power
+    /// supply of one enclosure.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to probe the enclosure
+    /// * `enclosure` - the enclosure to read the power supplies from
+    ///
+    pub fn get_psus_per_enclosure(
+        ctx: &Context,
+        enclosure: &Enclosure,
+    ) -> Result<Vec<EnclosurePsu>, JbodError> {
+        let status = read_status(ctx, enclosure)?;
+        Ok(psus_from_status(enclosure, &status))
+    }
+
+    /// Returns a vector with the EnclosureSensor structure for each sensor
+    /// of one enclosure.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to probe the enclosure
+    /// * `enclosure` - the enclosure to read the sensors from
+    ///
+    pub fn get_sensors_per_enclosure(
+        ctx: &Context,
+        enclosure: &Enclosure,
+    ) -> Result<Vec<EnclosureSensor>, JbodError> {
+        let status = read_status(ctx, enclosure)?;
+        Ok(sensors_from_status(enclosure, &status))
+    }
+
+    /// Returns a vector with the EnclosureAlarm structure for each audible
+    /// alarm of one enclosure.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to probe the enclosure
+    /// * `enclosure` - the enclosure to read the audible alarms from
+    ///
+    pub fn get_alarms_per_enclosure(
+        ctx: &Context,
+        enclosure: &Enclosure,
+    ) -> Result<Vec<EnclosureAlarm>, JbodError> {
+        let status = read_status(ctx, enclosure)?;
+        Ok(alarms_from_status(enclosure, &status))
+    }
+
+    /// Returns a vector with the EnclosureAlarm structure for each audible
+    /// alarm of every enclosure.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to probe the enclosures
+    ///
+    pub fn get_enclosure_alarm(ctx: &Context) -> Result<Vec<EnclosureAlarm>, JbodError> {
+        let mut enclosure_alarm = Vec::new();
+        for enclosure in get_enclosure(ctx)?.iter() {
+            enclosure_alarm.extend(get_alarms_per_enclosure(ctx, enclosure)?);
+        }
+
+        Ok(enclosure_alarm)
+    }
+
+    /// Returns a vector with the EnclosureSlot structure for each disk slot
+    /// of one enclosure, sorted by slot number.
+    ///
+    /// A slot where the enclosure reports a device but with no disk in
+    /// `enclosure.disks` is not responding, so the disks must be filled first.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to probe the enclosure
+    /// * `enclosure` - the enclosure to read the slots from, with its disks
+    ///
+    pub fn get_slots_per_enclosure(
+        ctx: &Context,
+        enclosure: &Enclosure,
+    ) -> Result<Vec<EnclosureSlot>, JbodError> {
+        let pages = read_pages(ctx, enclosure)?;
+        let mut slots = slots_from_status(enclosure, &pages.status, &pages.additional);
+        slots.sort_by_key(|s| s.number);
+
+        Ok(slots)
+    }
+
+    /// Fills the slots of an enclosure from the pages already read, sorted
+    /// by slot number, and copies the state bits of every slot element to
+    /// the disk plugged in it.
+    ///
+    /// # Arguments
+    ///
+    /// * `enclosure` - the enclosure to fill, with its disks
+    /// * `pages` - the enclosure pages of the enclosure
+    ///
+    pub fn fill_enclosure_slots(enclosure: &mut Enclosure, pages: &EnclosurePages) {
+        let mut slots = slots_from_status(enclosure, &pages.status, &pages.additional);
+        slots.sort_by_key(|s| s.number);
+        for disk in enclosure.disks.iter_mut() {
+            if let Some(slot) = slots.iter().find(|s| s.number == disk.slot) {
+                disk.flags = Some(slot.flags);
             }
-            if output.contains("revision") {
-                rev = output
-                    .replace("Product revision level:", "")
-                    .trim()
-                    .to_string();
+        }
+        enclosure.slots = slots;
+    }
+
+    /// Returns a vector with the EnclosureFan structure for each FAN.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to probe the enclosures
+    ///
+    pub fn get_enclosure_fan(ctx: &Context) -> Result<Vec<EnclosureFan>, JbodError> {
+        let mut enclosure_fan = Vec::new();
+        for enclosure in get_enclosure(ctx)?.iter() {
+            enclosure_fan.extend(get_fans_per_enclosure(ctx, enclosure)?);
+        }
+
+        Ok(enclosure_fan)
+    }
+
+    /// Returns the enclosure with the given serial number.
+    fn find_enclosure(ctx: &Context, serial: &str) -> Result<Enclosure, JbodError> {
+        get_enclosure(ctx)?
+            .into_iter()
+            .find(|enclosure| enclosure.serial.as_deref() == Some(serial))
+            .ok_or_else(|| JbodError::InvalidArgument(format!("enclosure {} not found", serial)))
+    }
+
+    /// Returns the position of an element in the status page from its
+    /// `sg_ses` index, example: "3,0".
+    fn element_position(status: &EnclosureStatus, index: &str) -> Option<usize> {
+        status
+            .elements
+            .iter()
+            .position(|element| element.sg_ses_index() == index)
+    }
+
+    /// Sends a control page to an enclosure and waits until `applied`
+    /// returns true for the enclosure status read back, the enclosure can
+    /// take a few seconds to act on it.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to send the page
+    /// * `enclosure` - the enclosure the page is sent to
+    /// * `page` - the control page
+    /// * `applied` - checks the status read back, returns the elements not applied yet
+    ///
+    fn send_control<F>(
+        ctx: &Context,
+        enclosure: &Enclosure,
+        page: &[u8],
+        applied: F,
+    ) -> Result<(), JbodError>
+    where
+        F: Fn(&EnclosureStatus) -> Vec<String>,
+    {
+        Ses::send_diagnostic(ctx, &enclosure.device_path, page)
+            .map_err(|err| JbodError::from_io(&enclosure.device_path, err))?;
+
+        let mut pending = Vec::new();
+        for attempt in 0..CONTROL_VERIFY_RETRIES {
+            if attempt > 0 {
+                thread::sleep(Duration::from_secs(1));
             }
-            if output.contains("serial") {
-                serial = output.replace("Unit serial number:", "").trim().to_string();
+            pending = applied(&read_status(ctx, enclosure)?);
+            if pending.is_empty() {
+                return Ok(());
             }
         }
 
-        return (vendor, ident, rev, serial);
+        Err(JbodError::ControlRejected(format!(
+            "{}: {}",
+            enclosure.device_path,
+            pending.join(", ")
+        )))
     }
 
-    /// Returns the fan speed(RPM) and a message provided by the jbod with extra information
-    /// about the FAN setup.
+    /// Sets the speed code of the FAN of an enclosure through the
+    /// enclosure control page and returns the page.
+    ///
+    /// Once the page is sent the FAN are read back until they run at the
+    /// requested speed code, a `JbodError::ControlRejected` is returned if
+    /// they never do.
     ///
     /// # Arguments
     ///
-    /// * `device_path` - The enclosure device
-    /// * `fan_index` - The fan slot on the JBOD
-    ///
-    fn get_enclosure_fan_speed(device_path: &str, fan_index: &str) -> (i64, String) {
-        let mut speed: i64 = 0;
-        let mut comment: String = String::new();
-
-        let index = format!("--index={}", &fan_index);
-        let sg_ses_cmd = Command::new(SG_SES)
-            .arg(index)
-            .arg(&device_path)
-            .output()
-            .expect("Failed to get fan speed");
-        let sg_ses_output = String::from_utf8_lossy(&sg_ses_cmd.stdout);
-        let output_spl: Vec<&str> = sg_ses_output.split("\n").collect();
-        for output in output_spl {
-            if output.contains("speed") {
-                let output_speed: Vec<&str> = output.split(",").collect();
-                let _speed = output_speed[1]
-                    .trim()
-                    .chars()
-                    .skip_while(|c| !c.is_digit(10))
-                    .take_while(|c| c.is_digit(10))
-                    .fold(None, |acc, c| {
-                        c.to_digit(10).map(|b| acc.unwrap_or(0) * 10 + b)
-                    });
-                speed = _speed.unwrap().into();
-                comment = output_speed[2].trim().to_string();
-            }
+    /// * `ctx` - the context used to probe the enclosure
+    /// * `serial` - the serial number of the enclosure
+    /// * `index` - the `sg_ses` index of one FAN, example: "3,0", or all of them
+    /// * `speed_code` - the requested speed code, from 1 (lowest) to 7 (highest)
+    /// * `dry_run` - only returns the page that would be sent
+    ///
+    /// # Example
+    /// ```ignore
+    /// let page = set_fan_speed(&ctx, "USWSJ03918EZ0069", Some("3,0"), 3, true)?;
+    /// ```
+    ///
+    pub fn set_fan_speed(
+        ctx: &Context,
+        serial: &str,
+        index: Option<&str>,
+        speed_code: u8,
+        dry_run: bool,
+    ) -> Result<Vec<u8>, JbodError> {
+        if !(1..=7).contains(&speed_code) {
+            return Err(JbodError::InvalidArgument(format!(
+                "speed code {} out of range, expected 1 to 7",
+                speed_code
+            )));
+        }
+        let enclosure = find_enclosure(ctx, serial)?;
+        let status = read_status(ctx, &enclosure)?;
+        let fans: Vec<EnclosureFan> = fans_from_status(&enclosure, &status)
+            .into_iter()
+            .filter(|fan| index.is_none_or(|index| fan.index == index))
+            .collect();
+        if fans.is_empty() {
+            return Err(JbodError::InvalidArgument(match index {
+                Some(index) => format!("no FAN {} on enclosure {}", index, serial),
+                None => format!("no FAN on enclosure {}", serial),
+            }));
+        }
+
+        let controls: Vec<(usize, [u8; 4])> = fans
+            .iter()
+            .filter_map(|fan| element_position(&status, &fan.index))
+            .map(|position| {
+                let control = Ses::cooling_control(&status.elements[position].status, speed_code);
+                (position, control)
+            })
+            .collect();
+        let page = Ses::encode_enclosure_control(&status, &controls);
+        if dry_run {
+            return Ok(page);
         }
-        return (speed, comment);
+
+        send_control(ctx, &enclosure, &page, |status| {
+            fans_from_status(&enclosure, status)
+                .into_iter()
+                .filter(|fan| fans.iter().any(|requested| requested.index == fan.index))
+                .filter(|fan| fan.speed_code != speed_code || fan.flags.off)
+                .map(|fan| format!("FAN {} at speed code {}", fan.index, fan.speed_code))
+                .collect()
+        })?;
+
+        Ok(page)
     }
 
-    /// Returns a vector with the EnclosureFan structure for each FAN.
+    /// Mutes or unmutes the audible alarms of an enclosure through the
+    /// enclosure control page and returns the page.
     ///
-    /// This function parses the output of sg_ses and collects information from
-    /// each FAN.
-    ///
-    pub fn get_enclosure_fan() -> Vec<EnclosureFan> {
-        let mut enclosure_fan: Vec<EnclosureFan> = Vec::new();
-
-        let enclosures = get_enclosure();
-        for enclosure in enclosures.iter() {
-            let cmd = format!("{} -j -ff {} | grep Cooling", SG_SES, enclosure.device_path);
-            let cmd_run = subprocess::Exec::shell(cmd.to_string())
-                .stream_stdout()
-                .unwrap();
-            let enc_fan = BufReader::new(cmd_run);
-
-            // Build regex
-            let re = Regex::new("(?P<desc>.*?)\\[(?P<id>-?\\d+,-?\\d+)\\].*Cooling").unwrap();
-
-            enc_fan.lines()
-                .filter_map(|l| l.ok())
-                .filter(|l| re.is_match(l.as_str()))
-                .for_each(|x| {
-                    let m = re.captures(x.as_str()).unwrap();
-                    if m.name("id").is_some() {
-                        let _idx = m.name("id").unwrap().as_str();
-                        let _desc = m.name("desc").unwrap().as_str(); // Empty string if no match
-                        let is_present =
-                            enclosure_fan.iter().any(|c| c.index == _idx && c.serial == enclosure.serial);
-                        if is_present == false {
-                            let (speed, comment): (i64, String) =
-                                get_enclosure_fan_speed(&enclosure.device_path, _idx);
-                            enclosure_fan.push(EnclosureFan {
-                                slot: enclosure.slot.clone(),
-                                serial: enclosure.serial.clone(),
-                                description: _desc.to_string(),
-                                index: _idx.to_string(),
-                                speed: speed,
-                                comment: comment,
-                            });
-                        }
-                    }
-                });
+    /// Once the page is sent the alarms are read back until they report
+    /// the requested mute, a `JbodError::ControlRejected` is returned if
+    /// they never do.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to probe the enclosure
+    /// * `serial` - the serial number of the enclosure
+    /// * `mute` - true to mute the alarms, false to unmute them
+    /// * `dry_run` - only returns the page that would be sent
+    ///
+    /// # Example
+    /// ```ignore
+    /// let page = set_alarm_mute(&ctx, "USWSJ03918EZ0069", true, false)?;
+    /// ```
+    ///
+    pub fn set_alarm_mute(
+        ctx: &Context,
+        serial: &str,
+        mute: bool,
+        dry_run: bool,
+    ) -> Result<Vec<u8>, JbodError> {
+        let enclosure = find_enclosure(ctx, serial)?;
+        let status = read_status(ctx, &enclosure)?;
+        let alarms = alarms_from_status(&enclosure, &status);
+        if alarms.is_empty() {
+            return Err(JbodError::InvalidArgument(format!(
+                "no audible alarm on enclosure {}",
+                serial
+            )));
+        }
+
+        let controls: Vec<(usize, [u8; 4])> = alarms
+            .iter()
+            .filter_map(|alarm| element_position(&status, &alarm.index))
+            .map(|position| {
+                let control = Ses::alarm_control(&status.elements[position].status, mute);
+                (position, control)
+            })
+            .collect();
+        let page = Ses::encode_enclosure_control(&status, &controls);
+        if dry_run {
+            return Ok(page);
+        }
+
+        send_control(ctx, &enclosure, &page, |status| {
+            alarms_from_status(&enclosure, status)
+                .into_iter()
+                .filter(|alarm| alarm.flags.muted != mute)
+                .map(|alarm| {
+                    let state = if alarm.flags.muted { "still muted" } else { "not muted" };
+                    format!("alarm {} {}", alarm.index, state)
+                })
+                .collect()
+        })?;
+
+        Ok(page)
+    }
+
+    /// Sets the hot spare, in critical array, in failed array and
+    /// rebuild/remap bits of the array device slots of an enclosure through
+    /// the enclosure control page.
+    ///
+    /// The slots not in `requested` are left as they are. Only the slots
+    /// whose bits differ are sent, so calling it again with the same request
+    /// sends nothing. Once the page is sent the slots are read back until
+    /// they report the requested bits, a `JbodError::ControlRejected` is
+    /// returned if they never do.
+    ///
+    /// Returns the slots that changed, or would change with `dry_run`, and
+    /// the control page, `None` when nothing changes.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to probe the enclosure
+    /// * `enclosure` - the enclosure, with its disks
+    /// * `requested` - the slot number and the requested bits of the slot
+    /// * `dry_run` - only returns the page that would be sent
+    ///
+    /// # Example
+    /// ```ignore
+    /// let rebuild = SlotStatus { rebuild_remap: true, ..SlotStatus::default() };
+    /// let (changes, page) = sync_slot_array_flags(&ctx, &enclosure, &[(17, rebuild)], true)?;
+    /// ```
+    ///
+    pub fn sync_slot_array_flags(
+        ctx: &Context,
+        enclosure: &Enclosure,
+        requested: &[(u32, SlotStatus)],
+        dry_run: bool,
+    ) -> Result<(Vec<SlotChange>, Option<Vec<u8>>), JbodError> {
+        let EnclosurePages { status, additional } = read_pages(ctx, enclosure)?;
+        let requested_bits = |number: u32| {
+            requested
+                .iter()
+                .find(|(slot, _)| *slot == number)
+                .map(|(_, bits)| *bits)
+        };
+
+        let mut changes = Vec::new();
+        let mut controls = Vec::new();
+        for slot in slots_from_status(enclosure, &status, &additional) {
+            let Some(position) = element_position(&status, &slot.index) else {
+                continue;
+            };
+            let element = &status.elements[position];
+            if element.element_type != ElementType::ArrayDeviceSlot {
+                continue;
+            }
+            let Some(bits) = requested_bits(slot.number) else {
+                continue;
+            };
+            if array_bits(&slot.flags, &bits).iter().all(|(_, current, req)| current == req) {
+                continue;
+            }
+            controls.push((position, Ses::array_slot_control(&element.status, &bits)));
+            changes.push(SlotChange {
+                device_map: enclosure
+                    .disks
+                    .iter()
+                    .find(|disk| disk.slot == slot.number)
+                    .and_then(|disk| disk.device_map.clone()),
+                slot,
+                requested: bits,
+            });
+        }
+        if controls.is_empty() {
+            return Ok((changes, None));
         }
-        enclosure_fan
+
+        let page = Ses::encode_enclosure_control(&status, &controls);
+        if dry_run {
+            return Ok((changes, Some(page)));
+        }
+
+        send_control(ctx, enclosure, &page, |status| {
+            slots_from_status(enclosure, status, &additional)
+                .into_iter()
+                .filter(|slot| changes.iter().any(|c| c.slot.index == slot.index))
+                .filter(|slot| {
+                    let bits = requested_bits(slot.number).unwrap_or_default();
+                    array_bits(&slot.flags, &bits).iter().any(|(_, current, req)| current != req)
+                })
+                .map(|slot| format!("slot {}", slot.number))
+                .collect()
+        })?;
+
+        Ok((changes, Some(page)))
     }
 
     /// Returns a vector with the Enclosure structure for each enclosure.
     ///
     /// This function parses `lsscsi` and calls `get_enclosure_details` to full
-    /// fill the Enclosure structure.
-    ///
-    pub fn get_enclosure() -> Vec<Enclosure> {
-        let lsscsi_cmd = Command::new(LSSCSI)
-            .args(&["-g"])
-            .output()
-            .expect("Failed to run get_enclosure()");
-        let lsscsi_output = String::from_utf8_lossy(&lsscsi_cmd.stdout);
+    /// fill the Enclosure structure, `disks`, `fans`, `psus`, `sensors` and
+    /// `slots` are left empty.
+    /// Lines without a scsi generic device are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to run `lsscsi` and INQUIRY
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::utils::runner::Runner::{Context, FixtureRunner};
+    /// use jbod::{BackPlane, Util};
+    ///
+    /// let standard = [&[0x0d, 0x00, 0x06, 0x02, 0x1f, 0x00, 0x40, 0x00][..],
+    ///     b"HGST    H4060-J         2033"].concat();
+    /// let serial = [&[0x0d, 0x80, 0x00, 0x10][..], b"USWSJ03918EZ0069"].concat();
+    /// let runner = FixtureRunner::new()
+    ///     .with_output(Util::LSSCSI, &["-g"],
+    ///         "[0:0:0:0]    enclosu HGST     H4060-J          2033  -          /dev/sg0\n")
+    ///     .with_scsi_data("/dev/sg0", &[0x12, 0x00], &standard)
+    ///     .with_scsi_data("/dev/sg0", &[0x12, 0x01, 0x00], &[0x0d, 0x00, 0x00, 0x02, 0x00, 0x80])
+    ///     .with_scsi_data("/dev/sg0", &[0x12, 0x01, 0x80], &serial);
+    /// let enclosures = BackPlane::get_enclosure(&Context::new(Box::new(runner))).unwrap();
+    ///
+    /// assert_eq!(enclosures[0].slot, "0:0:0:0");
+    /// assert_eq!(enclosures[0].model, "H4060-J");
+    /// assert_eq!(enclosures[0].serial.as_deref(), Some("USWSJ03918EZ0069"));
+    /// assert_eq!(enclosures[0].wwn, None);
+    /// ```
+    ///
+    pub fn get_enclosure(ctx: &Context) -> Result<Vec<Enclosure>, JbodError> {
+        let sysfsroot = format!("--sysfsroot={}", ctx.sysfs_root.display());
+        let mut lsscsi_args = vec!["-g"];
+        if ctx.sysfs_root != Path::new(SYSFS_ROOT) {
+            lsscsi_args.push(&sysfsroot);
+        }
+        let lsscsi_output = ctx
+            .runner
+            .run(LSSCSI, &lsscsi_args)
+            .map_err(|err| match err.kind() {
+                io::ErrorKind::NotFound => JbodError::MissingTool(LSSCSI.to_string()),
+                _ => JbodError::from_io(LSSCSI, err),
+            })?;
         let mut enclosure: Vec<Enclosure> = Vec::new();
 
         for p_output in lsscsi_output.split('\n') {
@@ -293,20 +1022,230 @@ pub mod BackPlane {
                 let mut s_output: Vec<&str> = p_output.split(' ').collect();
                 s_output.retain(|&content| !content.is_empty());
 
-                let device_index = s_output.iter().position(|&r| r.contains("/dev/")).unwrap();
-                let (_vendor, _ident, _rev, _serial) =
-                    get_enclosure_details(s_output[device_index].to_string());
+                let device_index = match s_output.iter().position(|&r| r.contains("/dev/")) {
+                    Some(index) => index,
+                    None => continue,
+                };
+                let device_path = s_output[device_index];
+                let sg_name = device_path.trim_start_matches("/dev/");
+                let sysfs_device = ctx.sys_class("scsi_generic").join(sg_name).join("device");
+                let inquiry = get_enclosure_details(ctx, device_path, &sysfs_device);
                 enclosure.push(Enclosure {
                     slot: s_output[0].to_string().replace(&['[', ']'][..], ""),
-                    device_path: s_output[device_index].to_string(),
-                    vendor: _vendor,
-                    model: _ident,
-                    revision: _rev,
-                    serial: _serial,
+                    device_path: device_path.to_string(),
+                    vendor: inquiry.as_ref().map(|i| i.vendor.clone()).unwrap_or_default(),
+                    model: inquiry.as_ref().map(|i| i.product.clone()).unwrap_or_default(),
+                    revision: inquiry.as_ref().map(|i| i.revision.clone()).unwrap_or_default(),
+                    serial: inquiry.as_ref().and_then(|i| i.serial.clone()),
+                    wwn: inquiry.and_then(|i| i.wwn),
+                    disks: Vec::new(),
+                    fans: Vec::new(),
+                    psus: Vec::new(),
+                    sensors: Vec::new(),
+                    slots: Vec::new(),
+                    errors: Vec::new(),
                 });
             }
         }
 
-        enclosure
+        Ok(enclosure)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::utils::runner::Runner::FixtureRunner;
+        use std::sync::Arc;
+
+        const SERIAL: &str = "USWSJ03918EZ0069";
+        const LSSCSI_OUTPUT: &str = "\
+[0:0:0:0]    enclosu HGST     H4060-J          2033  -          /dev/sg0
+[0:0:1:0]    disk    HGST     HUH721212AL4200  A3D0  /dev/sda   /dev/sg1
+[1:0:0:0]    enclosu HGST     H4060-J          2033  -          -
+[2:0:0:0]    enclosu HGST     H4060-J          2033  -          /dev/sg2
+";
+        const STATUS_CDB: [u8; 3] = [0x1c, 0x01, Ses::ENCLOSURE_STATUS_PAGE];
+
+        /// Returns the INQUIRY answers of an H4060-J enclosure.
+        fn with_inquiry(runner: FixtureRunner, device: &str, serial: &str) -> FixtureRunner {
+            let standard = [
+                &[0x0d, 0x00, 0x06, 0x02, 0x1f, 0x00, 0x40, 0x00][..],
+                b"HGST    H4060-J         2033",
+            ]
+            .concat();
+            let serial = [&[0x0d, 0x80, 0x00, serial.len() as u8][..], serial.as_bytes()].concat();
+            runner
+                .with_scsi_data(device, &[0x12, 0x00], &standard)
+                .with_scsi_data(device, &[0x12, 0x01, 0x00], &[0x0d, 0x00, 0x00, 0x02, 0x00, 0x80])
+                .with_scsi_data(device, &[0x12, 0x01, 0x80], &serial)
+        }
+
+        /// Returns a configuration page with one primary enclosure and a
+        /// type descriptor header for each element type and number of elements.
+        fn config_page(types: &[(u8, u8)]) -> Vec<u8> {
+            let mut page = vec![0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01];
+            page.extend([0x11, 0x00, types.len() as u8, 0x24]);
+            page.extend([0x50, 0x00, 0xcc, 0xa0, 0x5a, 0x7b, 0x1c, 0x3e]);
+            page.extend(b"HGST    H4060-J         2033");
+            for (element_type, possible_elements) in types {
+                page.extend([*element_type, *possible_elements, 0x00, 0x00]);
+            }
+            let len = (page.len() - 4) as u16;
+            page[2..4].copy_from_slice(&len.to_be_bytes());
+            page
+        }
+
+        /// Returns an enclosure status page with the given status elements,
+        /// overall elements included.
+        fn status_page(elements: &[[u8; 4]]) -> Vec<u8> {
+            let len = (4 + 4 * elements.len()) as u16;
+            let mut page = vec![0x02, 0x00];
+            page.extend(len.to_be_bytes());
+            page.extend([0x00, 0x00, 0x00, 0x01]);
+            page.extend(elements.concat());
+            page
+        }
+
+        /// Returns an enclosure with two FAN and one audible alarm, the FAN
+        /// run at `fan` and the alarm reports `alarm`.
+        fn enclosure_runner(fan: [u8; 4], alarm: [u8; 4]) -> FixtureRunner {
+            let overall = [0x00; 4];
+            let runner = FixtureRunner::new().with_output(LSSCSI, &["-g"], LSSCSI_OUTPUT);
+            with_inquiry(runner, "/dev/sg0", SERIAL)
+                .with_scsi_data("/dev/sg0", &[0x1c, 0x01, 0x01], &config_page(&[(0x03, 2), (0x06, 1)]))
+                .with_scsi_data(
+                    "/dev/sg0",
+                    &STATUS_CDB,
+                    &status_page(&[overall, fan, fan, overall, alarm]),
+                )
+        }
+
+        #[test]
+        fn get_enclosure_parses_lsscsi() {
+            let runner = FixtureRunner::new().with_output(LSSCSI, &["-g"], LSSCSI_OUTPUT);
+            let ctx = Context::new(Box::new(with_inquiry(runner, "/dev/sg0", SERIAL)));
+            let enclosures = get_enclosure(&ctx).unwrap();
+
+            assert_eq!(enclosures.len(), 2);
+            assert_eq!(enclosures[0].slot, "0:0:0:0");
+            assert_eq!(enclosures[0].device_path, "/dev/sg0");
+            assert_eq!(enclosures[0].vendor, "HGST");
+            assert_eq!(enclosures[0].serial.as_deref(), Some(SERIAL));
+            // The enclosure not answering INQUIRY is still listed.
+            assert_eq!(enclosures[1].slot, "2:0:0:0");
+            assert_eq!(enclosures[1].device_path, "/dev/sg2");
+            assert_eq!(enclosures[1].model, "");
+            assert_eq!(enclosures[1].serial, None);
+        }
+
+        #[test]
+        fn get_enclosure_passes_the_sysfs_root() {
+            let runner = FixtureRunner::new().with_output(
+                LSSCSI,
+                &["-g", "--sysfsroot=/tmp/sys"],
+                LSSCSI_OUTPUT,
+            );
+            let ctx = Context::new(Box::new(runner)).with_sysfs_root("/tmp/sys");
+
+            assert_eq!(get_enclosure(&ctx).unwrap().len(), 2);
+        }
+
+        #[test]
+        fn get_enclosure_without_lsscsi() {
+            let ctx = Context::new(Box::new(FixtureRunner::new()));
+
+            assert!(matches!(get_enclosure(&ctx), Err(JbodError::MissingTool(tool)) if tool == LSSCSI));
+        }
+
+        #[test]
+        fn find_enclosure_by_serial() {
+            let ctx = Context::new(Box::new(enclosure_runner([0x01, 0x03, 0xbe, 0x25], [0x01; 4])));
+
+            assert_eq!(find_enclosure(&ctx, SERIAL).unwrap().device_path, "/dev/sg0");
+            assert!(matches!(
+                find_enclosure(&ctx, "unknown"),
+                Err(JbodError::InvalidArgument(_))
+            ));
+        }
+
+        #[test]
+        fn set_fan_speed_dry_run() {
+            let runner = enclosure_runner([0x01, 0x83, 0xbe, 0x25], [0x01, 0x00, 0x00, 0x00]);
+            let ctx = Context::new(Box::new(runner));
+            let page = set_fan_speed(&ctx, SERIAL, Some("0,1"), 3, true).unwrap();
+
+            assert_eq!(
+                page,
+                vec![
+                    0x02, 0x00, 0x00, 0x18, 0x00, 0x00, 0x00, 0x01,
+                    0x00, 0x00, 0x00, 0x00,
+                    0x00, 0x00, 0x00, 0x00,
+                    0x80, 0x80, 0x00, 0x23,
+                    0x00, 0x00, 0x00, 0x00,
+                    0x00, 0x00, 0x00, 0x00,
+                ]
+            );
+        }
+
+        #[test]
+        fn set_fan_speed_invalid_arguments() {
+            let runner = enclosure_runner([0x01, 0x03, 0xbe, 0x25], [0x01, 0x00, 0x00, 0x00]);
+            let ctx = Context::new(Box::new(runner));
+
+            for (index, code) in [(None, 0), (None, 8), (Some("0,2"), 3), (Some("1,0"), 3)] {
+                assert!(matches!(
+                    set_fan_speed(&ctx, SERIAL, index, code, true),
+                    Err(JbodError::InvalidArgument(_))
+                ));
+            }
+        }
+
+        #[test]
+        fn set_fan_speed_reads_back_the_status() {
+            let alarm = [0x01, 0x00, 0x00, 0x00];
+            let applied = [0x01, 0x03, 0xbe, 0x23];
+            let runner = enclosure_runner([0x01, 0x03, 0xbe, 0x25], alarm).with_scsi_data_after_send(
+                "/dev/sg0",
+                &STATUS_CDB,
+                &status_page(&[[0x00; 4], applied, applied, [0x00; 4], alarm]),
+            );
+            let ctx = Context::new(Box::new(runner));
+            let page = set_fan_speed(&ctx, SERIAL, None, 3, false).unwrap();
+
+            assert_eq!(&page[12..20], &[0x80, 0x00, 0x00, 0x23, 0x80, 0x00, 0x00, 0x23]);
+        }
+
+        #[test]
+        fn set_fan_speed_rejected() {
+            let runner = enclosure_runner([0x01, 0x03, 0xbe, 0x25], [0x01, 0x00, 0x00, 0x00]);
+            let ctx = Context::new(Box::new(runner));
+
+            match set_fan_speed(&ctx, SERIAL, Some("0,0"), 3, false) {
+                Err(JbodError::ControlRejected(pending)) => {
+                    assert_eq!(pending, "/dev/sg0: FAN 0,0 at speed code 5")
+                }
+                other => panic!("expected ControlRejected, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn set_alarm_mute_sends_the_control_page() {
+            let fan = [0x01, 0x03, 0xbe, 0x25];
+            let muted = [0x01, 0x00, 0x00, 0x52];
+            let runner = enclosure_runner(fan, [0x01, 0x00, 0x00, 0x12]).with_scsi_data_after_send(
+                "/dev/sg0",
+                &STATUS_CDB,
+                &status_page(&[[0x00; 4], fan, fan, [0x00; 4], muted]),
+            );
+            let runner = Arc::new(runner);
+            let ctx = Context::new(Box::new(Arc::clone(&runner)));
+            let page = set_alarm_mute(&ctx, SERIAL, true, false).unwrap();
+            let sent = runner.sent();
+
+            assert_eq!(&page[24..28], &[0x80, 0x00, 0x00, 0x52]);
+            assert_eq!(sent.len(), 1);
+            assert_eq!(sent[0].1, vec![0x1d, 0x10, 0x00, 0x00, 0x1c, 0x00]);
+            assert_eq!(sent[0].2, page);
+        }
     }
 }
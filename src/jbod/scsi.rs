@@ -0,0 +1,463 @@
+/*-
+ * SPDX-License-Identifier: BSD-2-Clause
+ *
+ * BSD 2-Clause License
+ *
+ * Copyright (c) 2021-2023, Gandi S.A.S.
+ * All rights reserved.
+ *
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice, this
+ *    list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+ * AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+ * IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+ * FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+ * DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+ * SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+ * CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+ * OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+#[allow(non_snake_case)]
+pub mod Scsi {
+    use std::fs;
+    use std::io;
+    use std::path::Path;
+
+    use crate::utils::runner::Runner::Context;
+
+    pub const INQUIRY: u8 = 0x12;
+    pub const SUPPORTED_VPD_PAGES: u8 = 0x00;
+    pub const UNIT_SERIAL_NUMBER_VPD: u8 = 0x80;
+    pub const DEVICE_IDENTIFICATION_VPD: u8 = 0x83;
+    pub const LOG_SENSE: u8 = 0x4d;
+    pub const TEMPERATURE_LOG_PAGE: u8 = 0x0d;
+
+    /// Allocation length of the standard INQUIRY, the same used by `sg_inq`.
+    const STANDARD_INQUIRY_LEN: usize = 96;
+    const VPD_PAGE_LEN: usize = 512;
+    const LOG_PAGE_LEN: usize = 252;
+
+    /// The standard INQUIRY data.
+    #[derive(Debug, Clone)]
+    pub struct StandardInquiry {
+        /// Peripheral device type, example: 0x00 for a disk, 0x0d for an enclosure.
+        pub device_type: u8,
+        pub vendor: String,
+        pub product: String,
+        pub revision: String,
+    }
+
+    /// Association of a designator from the device identification VPD page.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Association {
+        LogicalUnit,
+        TargetPort,
+        TargetDevice,
+        Reserved,
+    }
+
+    /// Type of a designator from the device identification VPD page.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum DesignatorType {
+        VendorSpecific,
+        T10VendorId,
+        Eui64,
+        Naa,
+        RelativeTargetPort,
+        TargetPortGroup,
+        LogicalUnitGroup,
+        Md5LogicalUnit,
+        ScsiName,
+        ProtocolSpecificPort,
+        Other(u8),
+    }
+
+    /// A designation descriptor from the device identification VPD page (0x83).
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Designator {
+        pub association: Association,
+        pub designator_type: DesignatorType,
+        /// The designator is ASCII or UTF-8 text instead of binary.
+        pub text: bool,
+        pub value: Vec<u8>,
+    }
+
+    impl Designator {
+        /// Returns the designator as printed by `sg_inq`, binary designators
+        /// are written in hexadecimal, example: 0x5000cca26b1ac3a8
+        pub fn to_id(&self) -> String {
+            if self.text {
+                String::from_utf8_lossy(&self.value)
+                    .trim_matches(|c: char| c.is_whitespace() || c == '\0')
+                    .to_string()
+            } else {
+                let hex: String = self.value.iter().map(|b| format!("{:02x}", b)).collect();
+                format!("0x{}", hex)
+            }
+        }
+    }
+
+    /// Everything we know from INQUIRY about a device.
+    #[derive(Debug, Clone)]
+    pub struct Inquiry {
+        pub device_type: u8,
+        pub vendor: String,
+        pub product: String,
+        pub revision: String,
+        /// The unit serial number (VPD 0x80), if the device provides it.
+        pub serial: Option<String>,
+        /// The NAA designator of the logical unit (VPD 0x83), example: 0x5000cca26b1ac3a8
+        pub wwn: Option<String>,
+        pub designators: Vec<Designator>,
+    }
+
+    /// The temperature log page (0x0D), both values are in Celsius.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Temperature {
+        /// The current temperature, `None` if the disk cannot report it.
+        pub current: Option<i32>,
+        /// The reference (trip) temperature given by the manufacturer.
+        pub reference: Option<i32>,
+    }
+
+    fn invalid_data(msg: String) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, msg)
+    }
+
+    /// Returns the printable text of an ASCII field without the padding.
+    fn ascii(bytes: &[u8]) -> String {
+        String::from_utf8_lossy(bytes)
+            .trim_matches(|c: char| c.is_whitespace() || c == '\0')
+            .to_string()
+    }
+
+    /// Returns the payload of a VPD page after checking its page code.
+    fn vpd_payload(data: &[u8], page_code: u8) -> io::Result<&[u8]> {
+        if data.len() < 4 || data[1] != page_code {
+            return Err(invalid_data(format!("invalid VPD page 0x{:02x}", page_code)));
+        }
+        let len = u16::from_be_bytes([data[2], data[3]]) as usize;
+        Ok(&data[4..(4 + len).min(data.len())])
+    }
+
+    /// Returns the decoded standard INQUIRY data.
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::Scsi::decode_standard_inquiry;
+    ///
+    /// let data = [
+    ///     &[0x0d, 0x00, 0x06, 0x02, 0x1f, 0x00, 0x40, 0x00][..],
+    ///     b"HGST    H4060-J         2033",
+    /// ]
+    /// .concat();
+    /// let inquiry = decode_standard_inquiry(&data).unwrap();
+    ///
+    /// assert_eq!(inquiry.device_type, 0x0d);
+    /// assert_eq!(inquiry.vendor, "HGST");
+    /// assert_eq!(inquiry.product, "H4060-J");
+    /// assert_eq!(inquiry.revision, "2033");
+    /// ```
+    pub fn decode_standard_inquiry(data: &[u8]) -> io::Result<StandardInquiry> {
+        if data.len() < 36 {
+            return Err(invalid_data(format!(
+                "standard inquiry too short: {} bytes",
+                data.len()
+            )));
+        }
+        Ok(StandardInquiry {
+            device_type: data[0] & 0x1f,
+            vendor: ascii(&data[8..16]),
+            product: ascii(&data[16..32]),
+            revision: ascii(&data[32..36]),
+        })
+    }
+
+    /// Returns the VPD pages listed by the supported VPD pages page (0x00).
+    pub fn decode_supported_vpd_pages(data: &[u8]) -> io::Result<Vec<u8>> {
+        Ok(vpd_payload(data, SUPPORTED_VPD_PAGES)?.to_vec())
+    }
+
+    /// Returns the serial number from the unit serial number VPD page (0x80).
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::Scsi::decode_unit_serial_number;
+    ///
+    /// let data = [&[0x00, 0x80, 0x00, 0x0c][..], b"  1EKTL0DZ  "].concat();
+    /// assert_eq!(decode_unit_serial_number(&data).unwrap(), "1EKTL0DZ");
+    /// ```
+    pub fn decode_unit_serial_number(data: &[u8]) -> io::Result<String> {
+        Ok(ascii(vpd_payload(data, UNIT_SERIAL_NUMBER_VPD)?))
+    }
+
+    /// Returns the designators of the device identification VPD page (0x83).
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::Scsi::{decode_device_identification, Association, DesignatorType};
+    ///
+    /// let data = [
+    ///     0x00, 0x83, 0x00, 0x0c,
+    ///     0x01, 0x03, 0x00, 0x08, 0x50, 0x00, 0xcc, 0xa2, 0x6b, 0x1a, 0xc3, 0xa8,
+    /// ];
+    /// let designators = decode_device_identification(&data).unwrap();
+    ///
+    /// assert_eq!(designators[0].association, Association::LogicalUnit);
+    /// assert_eq!(designators[0].designator_type, DesignatorType::Naa);
+    /// assert_eq!(designators[0].to_id(), "0x5000cca26b1ac3a8");
+    /// ```
+    pub fn decode_device_identification(data: &[u8]) -> io::Result<Vec<Designator>> {
+        let payload = vpd_payload(data, DEVICE_IDENTIFICATION_VPD)?;
+        let mut designators = Vec::new();
+        let mut offset = 0;
+
+        while offset + 4 <= payload.len() {
+            let header = &payload[offset..offset + 4];
+            let len = header[3] as usize;
+            let value = payload
+                .get(offset + 4..offset + 4 + len)
+                .ok_or_else(|| invalid_data("device identification page truncated".to_string()))?;
+            let association = match (header[1] >> 4) & 0x03 {
+                0 => Association::LogicalUnit,
+                1 => Association::TargetPort,
+                2 => Association::TargetDevice,
+                _ => Association::Reserved,
+            };
+            let designator_type = match header[1] & 0x0f {
+                0x0 => DesignatorType::VendorSpecific,
+                0x1 => DesignatorType::T10VendorId,
+                0x2 => DesignatorType::Eui64,
+                0x3 => DesignatorType::Naa,
+                0x4 => DesignatorType::RelativeTargetPort,
+                0x5 => DesignatorType::TargetPortGroup,
+                0x6 => DesignatorType::LogicalUnitGroup,
+                0x7 => DesignatorType::Md5LogicalUnit,
+                0x8 => DesignatorType::ScsiName,
+                0x9 => DesignatorType::ProtocolSpecificPort,
+                other => DesignatorType::Other(other),
+            };
+            designators.push(Designator {
+                association,
+                designator_type,
+                text: matches!(header[0] & 0x0f, 0x2 | 0x3),
+                value: value.to_vec(),
+            });
+            offset += 4 + len;
+        }
+
+        Ok(designators)
+    }
+
+    /// Returns the data of an INQUIRY command.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to send the command
+    /// * `device` - the scsi generic device, example: /dev/sg9
+    /// * `vpd_page` - the VPD page we want, `None` for the standard INQUIRY data
+    ///
+    pub fn read_inquiry(ctx: &Context, device: &str, vpd_page: Option<u8>) -> io::Result<Vec<u8>> {
+        let (evpd, page, len) = match vpd_page {
+            Some(page) => (0x01, page, VPD_PAGE_LEN),
+            None => (0x00, 0x00, STANDARD_INQUIRY_LEN),
+        };
+        let cdb = [INQUIRY, evpd, page, (len >> 8) as u8, (len & 0xff) as u8, 0x00];
+        ctx.scsi_in(device, &cdb, len)
+    }
+
+    /// Returns the standard INQUIRY data of a device plus its unit serial
+    /// number and designators when the device supports those VPD pages.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to send the commands
+    /// * `device` - the scsi generic device, example: /dev/sg9
+    ///
+    pub fn inquiry(ctx: &Context, device: &str) -> io::Result<Inquiry> {
+        let standard = decode_standard_inquiry(&read_inquiry(ctx, device, None)?)?;
+        let supported = read_inquiry(ctx, device, Some(SUPPORTED_VPD_PAGES))
+            .and_then(|data| decode_supported_vpd_pages(&data))
+            .unwrap_or_default();
+
+        let mut serial = None;
+        if supported.contains(&UNIT_SERIAL_NUMBER_VPD) {
+            serial = read_inquiry(ctx, device, Some(UNIT_SERIAL_NUMBER_VPD))
+                .and_then(|data| decode_unit_serial_number(&data))
+                .ok()
+                .filter(|serial| !serial.is_empty());
+        }
+
+        let mut designators = Vec::new();
+        if supported.contains(&DEVICE_IDENTIFICATION_VPD) {
+            designators = read_inquiry(ctx, device, Some(DEVICE_IDENTIFICATION_VPD))
+                .and_then(|data| decode_device_identification(&data))
+                .unwrap_or_default();
+        }
+        let wwn = logical_unit_naa(&designators);
+
+        Ok(Inquiry {
+            device_type: standard.device_type,
+            vendor: standard.vendor,
+            product: standard.product,
+            revision: standard.revision,
+            serial,
+            wwn,
+            designators,
+        })
+    }
+
+    /// Returns the NAA designator of the logical unit, example: 0x5000cca26b1ac3a8
+    fn logical_unit_naa(designators: &[Designator]) -> Option<String> {
+        designators
+            .iter()
+            .find(|d| {
+                d.association == Association::LogicalUnit
+                    && d.designator_type == DesignatorType::Naa
+            })
+            .map(Designator::to_id)
+    }
+
+    /// Returns a sysfs attribute of a scsi device without the padding.
+    fn sysfs_attribute(device: &Path, attribute: &str) -> io::Result<String> {
+        fs::read_to_string(device.join(attribute)).map(|content| content.trim().to_string())
+    }
+
+    /// Returns what the kernel saved of the INQUIRY data of a scsi device,
+    /// the VPD pages are the ones cached in `vpd_pg80` and `vpd_pg83`.
+    ///
+    /// # Arguments
+    ///
+    /// * `device` - the sysfs directory of the scsi device, example: /sys/class/scsi_generic/sg9/device
+    ///
+    pub fn sysfs_inquiry(device: &Path) -> io::Result<Inquiry> {
+        let vendor = sysfs_attribute(device, "vendor")?;
+        let serial = fs::read(device.join("vpd_pg80"))
+            .and_then(|data| decode_unit_serial_number(&data))
+            .ok()
+            .filter(|serial| !serial.is_empty());
+        let designators = fs::read(device.join("vpd_pg83"))
+            .and_then(|data| decode_device_identification(&data))
+            .unwrap_or_default();
+
+        Ok(Inquiry {
+            device_type: sysfs_attribute(device, "type")
+                .ok()
+                .and_then(|device_type| device_type.parse().ok())
+                .unwrap_or_default(),
+            vendor,
+            product: sysfs_attribute(device, "model").unwrap_or_default(),
+            revision: sysfs_attribute(device, "rev").unwrap_or_default(),
+            serial,
+            wwn: logical_unit_naa(&designators),
+            designators,
+        })
+    }
+
+    /// Returns the INQUIRY data of a device, from sysfs when the context
+    /// does not send SCSI commands or when the device does not answer.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to send the commands
+    /// * `device` - the scsi generic device, example: /dev/sg9
+    /// * `sysfs_device` - the sysfs directory of the same scsi device
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::utils::runner::Runner::{Context, FixtureRunner};
+    /// use jbod::Scsi::device_inquiry;
+    ///
+    /// let ctx = Context::new(Box::new(FixtureRunner::new())).with_sysfs_root("/tmp/sys");
+    /// assert!(device_inquiry(&ctx, "/dev/sg9", "/tmp/sys/class/scsi_generic/sg9/device".as_ref()).is_err());
+    /// ```
+    pub fn device_inquiry(ctx: &Context, device: &str, sysfs_device: &Path) -> io::Result<Inquiry> {
+        if ctx.send_scsi {
+            inquiry(ctx, device).or_else(|_| sysfs_inquiry(sysfs_device))
+        } else {
+            sysfs_inquiry(sysfs_device)
+        }
+    }
+
+    /// Returns the current and reference temperature of the temperature log page.
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::Scsi::decode_temperature_log;
+    ///
+    /// let data = [
+    ///     0x0d, 0x00, 0x00, 0x0c,
+    ///     0x00, 0x00, 0x03, 0x02, 0x00, 0x24,
+    ///     0x00, 0x01, 0x03, 0x02, 0x00, 0x3c,
+    /// ];
+    /// let temperature = decode_temperature_log(&data).unwrap();
+    ///
+    /// assert_eq!(temperature.current, Some(36));
+    /// assert_eq!(temperature.reference, Some(60));
+    /// ```
+    pub fn decode_temperature_log(data: &[u8]) -> io::Result<Temperature> {
+        if data.len() < 4 || data[0] & 0x3f != TEMPERATURE_LOG_PAGE {
+            return Err(invalid_data("invalid temperature log page".to_string()));
+        }
+        let len = u16::from_be_bytes([data[2], data[3]]) as usize;
+        let params = &data[4..(4 + len).min(data.len())];
+        let mut temperature = Temperature {
+            current: None,
+            reference: None,
+        };
+        let mut offset = 0;
+
+        while offset + 4 <= params.len() {
+            let code = u16::from_be_bytes([params[offset], params[offset + 1]]);
+            let param_len = params[offset + 3] as usize;
+            // The value is the second byte of the parameter, 0xff means not available.
+            let value = params
+                .get(offset + 5)
+                .filter(|_| param_len >= 2)
+                .filter(|value| **value != 0xff)
+                .map(|value| *value as i32);
+            match code {
+                0x0000 => temperature.current = value,
+                0x0001 => temperature.reference = value,
+                _ => {}
+            }
+            offset += 4 + param_len;
+        }
+
+        Ok(temperature)
+    }
+
+    /// Returns the temperature of a disk read with LOG SENSE.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to send the command
+    /// * `device` - the scsi generic device, example: /dev/sg100
+    ///
+    pub fn read_temperature(ctx: &Context, device: &str) -> io::Result<Temperature> {
+        let cdb = [
+            LOG_SENSE,
+            0x00,
+            0x40 | TEMPERATURE_LOG_PAGE, // PC 01b, cumulative values
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            (LOG_PAGE_LEN >> 8) as u8,
+            (LOG_PAGE_LEN & 0xff) as u8,
+            0x00,
+        ];
+        decode_temperature_log(&ctx.scsi_in(device, &cdb, LOG_PAGE_LEN)?)
+    }
+}
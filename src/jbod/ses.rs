@@ -0,0 +1,1381 @@
+/*-
+ * SPDX-License-Identifier: BSD-2-Clause
+ *
+ * BSD 2-Clause License
+ *
+ * Copyright (c) 2021-2023, Gandi S.A.S.
+ * All rights reserved.
+ *
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice, this
+ *    list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+ * AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+ * IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+ * FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+ * DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+ * SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+ * CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+ * OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+#[allow(non_snake_case)]
+pub mod Ses {
+    use serde::{Serialize, Serializer};
+    use std::fmt;
+    use std::io;
+
+    use crate::utils::runner::Runner::Context;
+
+    pub const RECEIVE_DIAGNOSTIC_RESULTS: u8 = 0x1c;
+    pub const SEND_DIAGNOSTIC: u8 = 0x1d;
+    pub const CONFIGURATION_PAGE: u8 = 0x01;
+    pub const ENCLOSURE_STATUS_PAGE: u8 = 0x02;
+    pub const ENCLOSURE_CONTROL_PAGE: u8 = 0x02;
+    pub const ELEMENT_DESCRIPTOR_PAGE: u8 = 0x07;
+    pub const ADDITIONAL_ELEMENT_STATUS_PAGE: u8 = 0x0a;
+
+    /// The protocol identifier of SAS in the additional element status page.
+    const SAS_PROTOCOL: u8 = 0x06;
+    /// The length of a SAS phy descriptor in the additional element status page.
+    const SAS_PHY_DESCRIPTOR_LEN: usize = 28;
+
+    /// The largest page we ask for, the same limit used by `sg_ses`.
+    const MAX_PAGE_LEN: usize = 65532;
+
+    /// Actual speed code of a cooling element as described by `sg_ses`.
+    const FAN_SPEED_CODE_DESC: [&str; 8] = [
+        "stopped",
+        "at lowest speed",
+        "at second lowest speed",
+        "at third lowest speed",
+        "at intermediate speed",
+        "at third highest speed",
+        "at second highest speed",
+        "at highest speed",
+    ];
+
+    /// The element types defined by SES-3.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ElementType {
+        Unspecified,
+        DeviceSlot,
+        PowerSupply,
+        Cooling,
+        TemperatureSensor,
+        DoorLock,
+        AudibleAlarm,
+        EnclosureServicesController,
+        ScsiServicesController,
+        NonvolatileCache,
+        InvalidOperationReason,
+        UninterruptiblePowerSupply,
+        Display,
+        KeyPadEntry,
+        Enclosure,
+        ScsiPortTransceiver,
+        Language,
+        CommunicationPort,
+        VoltageSensor,
+        CurrentSensor,
+        ScsiTargetPort,
+        ScsiInitiatorPort,
+        SimpleSubenclosure,
+        ArrayDeviceSlot,
+        SasExpander,
+        SasConnector,
+        Other(u8),
+    }
+
+    impl From<u8> for ElementType {
+        fn from(code: u8) -> Self {
+            match code {
+                0x00 => ElementType::Unspecified,
+                0x01 => ElementType::DeviceSlot,
+                0x02 => ElementType::PowerSupply,
+                0x03 => ElementType::Cooling,
+                0x04 => ElementType::TemperatureSensor,
+                0x05 => ElementType::DoorLock,
+                0x06 => ElementType::AudibleAlarm,
+                0x07 => ElementType::EnclosureServicesController,
+                0x08 => ElementType::ScsiServicesController,
+                0x09 => ElementType::NonvolatileCache,
+                0x0a => ElementType::InvalidOperationReason,
+                0x0b => ElementType::UninterruptiblePowerSupply,
+                0x0c => ElementType::Display,
+                0x0d => ElementType::KeyPadEntry,
+                0x0e => ElementType::Enclosure,
+                0x0f => ElementType::ScsiPortTransceiver,
+                0x10 => ElementType::Language,
+                0x11 => ElementType::CommunicationPort,
+                0x12 => ElementType::VoltageSensor,
+                0x13 => ElementType::CurrentSensor,
+                0x14 => ElementType::ScsiTargetPort,
+                0x15 => ElementType::ScsiInitiatorPort,
+                0x16 => ElementType::SimpleSubenclosure,
+                0x17 => ElementType::ArrayDeviceSlot,
+                0x18 => ElementType::SasExpander,
+                0x19 => ElementType::SasConnector,
+                code => ElementType::Other(code),
+            }
+        }
+    }
+
+    /// The status code carried in the first byte of every status element.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ElementStatusCode {
+        Unsupported,
+        Ok,
+        Critical,
+        NonCritical,
+        Unrecoverable,
+        NotInstalled,
+        Unknown,
+        NotAvailable,
+        NoAccessAllowed,
+        Reserved(u8),
+    }
+
+    impl From<u8> for ElementStatusCode {
+        fn from(code: u8) -> Self {
+            match code & 0x0f {
+                0x0 => ElementStatusCode::Unsupported,
+                0x1 => ElementStatusCode::Ok,
+                0x2 => ElementStatusCode::Critical,
+                0x3 => ElementStatusCode::NonCritical,
+                0x4 => ElementStatusCode::Unrecoverable,
+                0x5 => ElementStatusCode::NotInstalled,
+                0x6 => ElementStatusCode::Unknown,
+                0x7 => ElementStatusCode::NotAvailable,
+                0x8 => ElementStatusCode::NoAccessAllowed,
+                code => ElementStatusCode::Reserved(code),
+            }
+        }
+    }
+
+    impl fmt::Display for ElementStatusCode {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                ElementStatusCode::Unsupported => write!(f, "Unsupported"),
+                ElementStatusCode::Ok => write!(f, "OK"),
+                ElementStatusCode::Critical => write!(f, "Critical"),
+                ElementStatusCode::NonCritical => write!(f, "Noncritical"),
+                ElementStatusCode::Unrecoverable => write!(f, "Unrecoverable"),
+                ElementStatusCode::NotInstalled => write!(f, "Not installed"),
+                ElementStatusCode::Unknown => write!(f, "Unknown"),
+                ElementStatusCode::NotAvailable => write!(f, "Not available"),
+                ElementStatusCode::NoAccessAllowed => write!(f, "No access allowed"),
+                ElementStatusCode::Reserved(code) => write!(f, "Reserved [0x{:x}]", code),
+            }
+        }
+    }
+
+    impl ElementStatusCode {
+        /// Returns the status code as defined by SES-3, example: 1 for OK.
+        pub fn code(&self) -> u8 {
+            match self {
+                ElementStatusCode::Unsupported => 0x0,
+                ElementStatusCode::Ok => 0x1,
+                ElementStatusCode::Critical => 0x2,
+                ElementStatusCode::NonCritical => 0x3,
+                ElementStatusCode::Unrecoverable => 0x4,
+                ElementStatusCode::NotInstalled => 0x5,
+                ElementStatusCode::Unknown => 0x6,
+                ElementStatusCode::NotAvailable => 0x7,
+                ElementStatusCode::NoAccessAllowed => 0x8,
+                ElementStatusCode::Reserved(code) => *code,
+            }
+        }
+    }
+
+    /// The status is serialized as printed, example: "OK".
+    impl Serialize for ElementStatusCode {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_str(self)
+        }
+    }
+
+    /// An enclosure descriptor from the configuration page.
+    #[derive(Debug, Clone)]
+    pub struct EnclosureDescriptor {
+        pub subenclosure_id: u8,
+        /// The enclosure logical identifier, usually its WWN.
+        pub logical_id: u64,
+        pub vendor: String,
+        pub product: String,
+        pub revision: String,
+    }
+
+    /// A type descriptor header from the configuration page with its text.
+    #[derive(Debug, Clone)]
+    pub struct TypeDescriptor {
+        pub element_type: ElementType,
+        pub possible_elements: u8,
+        pub subenclosure_id: u8,
+        pub text: String,
+    }
+
+    /// The configuration diagnostic page (0x01).
+    #[derive(Debug, Clone)]
+    pub struct Configuration {
+        pub generation: u32,
+        pub enclosures: Vec<EnclosureDescriptor>,
+        pub types: Vec<TypeDescriptor>,
+    }
+
+    /// One element of the enclosure, joined from the configuration, the
+    /// enclosure status and the element descriptor pages.
+    #[derive(Debug, Clone)]
+    pub struct Element {
+        pub element_type: ElementType,
+        /// Position of the type descriptor header in the configuration page.
+        pub type_index: usize,
+        /// Position of the element within its type, `None` for the overall element.
+        pub index: Option<usize>,
+        pub subenclosure_id: u8,
+        /// Text from the element descriptor page, empty if not provided.
+        pub descriptor: String,
+        /// The raw status element.
+        pub status: [u8; 4],
+    }
+
+    impl Element {
+        pub fn status_code(&self) -> ElementStatusCode {
+            ElementStatusCode::from(self.status[0])
+        }
+
+        pub fn predicted_failure(&self) -> bool {
+            self.status[0] & 0x40 != 0
+        }
+
+        pub fn disabled(&self) -> bool {
+            self.status[0] & 0x20 != 0
+        }
+
+        pub fn swap(&self) -> bool {
+            self.status[0] & 0x10 != 0
+        }
+
+        /// Returns the element index as printed by `sg_ses`, example: "3,0",
+        /// the overall element uses -1.
+        pub fn sg_ses_index(&self) -> String {
+            match self.index {
+                Some(index) => format!("{},{}", self.type_index, index),
+                None => format!("{},-1", self.type_index),
+            }
+        }
+    }
+
+    /// The enclosure status diagnostic page (0x02).
+    #[derive(Debug, Clone)]
+    pub struct EnclosureStatus {
+        pub generation: u32,
+        pub invop: bool,
+        pub info: bool,
+        pub non_critical: bool,
+        pub critical: bool,
+        pub unrecoverable: bool,
+        pub elements: Vec<Element>,
+    }
+
+    /// The SAS additional element status of a device slot, from the
+    /// additional element status page (0x0A).
+    #[derive(Debug, Clone)]
+    pub struct AdditionalStatus {
+        /// Position of the element in the elements of `decode_enclosure_status`.
+        pub element: usize,
+        /// The device slot number reported by the enclosure.
+        pub device_slot: u8,
+        /// The SAS address of every phy of the slot, 0 when nothing is attached.
+        pub sas_addresses: Vec<u64>,
+    }
+
+    /// The pages describing an enclosure, read by `read_enclosure_pages`.
+    #[derive(Debug, Clone)]
+    pub struct EnclosurePages {
+        /// The enclosure status, with the element descriptors filled in.
+        pub status: EnclosureStatus,
+        /// The additional element status, empty when the enclosure does not
+        /// provide the page.
+        pub additional: Vec<AdditionalStatus>,
+    }
+
+    fn invalid_data(msg: String) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, msg)
+    }
+
+    fn be16(bytes: &[u8]) -> u16 {
+        u16::from_be_bytes([bytes[0], bytes[1]])
+    }
+
+    fn be32(bytes: &[u8]) -> u32 {
+        u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    fn be64(bytes: &[u8]) -> u64 {
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(&bytes[..8]);
+        u64::from_be_bytes(buf)
+    }
+
+    /// Returns the printable text of an ASCII field without the padding.
+    fn ascii(bytes: &[u8]) -> String {
+        String::from_utf8_lossy(bytes)
+            .trim_matches(|c: char| c.is_whitespace() || c == '\0')
+            .to_string()
+    }
+
+    /// Returns the page without the bytes beyond its page length.
+    ///
+    /// # Arguments
+    ///
+    /// * `page` - the raw diagnostic page
+    /// * `page_code` - the page code we expect in the first byte
+    ///
+    fn check_page(page: &[u8], page_code: u8) -> io::Result<&[u8]> {
+        if page.len() < 8 {
+            return Err(invalid_data(format!(
+                "page 0x{:02x} too short: {} bytes",
+                page_code,
+                page.len()
+            )));
+        }
+        if page[0] != page_code {
+            return Err(invalid_data(format!(
+                "expected page 0x{:02x}, got 0x{:02x}",
+                page_code, page[0]
+            )));
+        }
+        let len = (be16(&page[2..4]) as usize + 4).min(page.len());
+        Ok(&page[..len])
+    }
+
+    /// Returns the decoded configuration diagnostic page.
+    ///
+    /// # Arguments
+    ///
+    /// * `page` - the raw page as returned by RECEIVE DIAGNOSTIC RESULTS
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::Ses::{decode_configuration, ElementType};
+    ///
+    /// let page = [
+    ///     &[0x01, 0x00, 0x00, 0x38, 0x00, 0x00, 0x00, 0x01][..],
+    ///     &[0x11, 0x00, 0x02, 0x24],
+    ///     &[0x50, 0x00, 0xcc, 0xa0, 0x5a, 0x7b, 0x1c, 0x3e],
+    ///     b"HGST    H4060-J         2033",
+    ///     &[0x03, 0x02, 0x00, 0x04, 0x17, 0x3c, 0x00, 0x00],
+    ///     b"Fans",
+    /// ]
+    /// .concat();
+    /// let config = decode_configuration(&page).unwrap();
+    ///
+    /// assert_eq!(config.generation, 1);
+    /// assert_eq!(config.enclosures[0].logical_id, 0x5000cca05a7b1c3e);
+    /// assert_eq!(config.enclosures[0].product, "H4060-J");
+    /// assert_eq!(config.types[0].element_type, ElementType::Cooling);
+    /// assert_eq!(config.types[0].text, "Fans");
+    /// assert_eq!(config.types[1].element_type, ElementType::ArrayDeviceSlot);
+    /// assert_eq!(config.types[1].possible_elements, 60);
+    /// ```
+    ///
+    pub fn decode_configuration(page: &[u8]) -> io::Result<Configuration> {
+        let page = check_page(page, CONFIGURATION_PAGE)?;
+        let truncated = || invalid_data("configuration page truncated".to_string());
+        let generation = be32(&page[4..8]);
+        let mut enclosures = Vec::new();
+        let mut type_headers = 0;
+        let mut offset = 8;
+
+        // The primary enclosure plus the secondary subenclosures.
+        for _ in 0..=page[1] {
+            let desc = page.get(offset..offset + 4).ok_or_else(truncated)?;
+            let desc_len = desc[3] as usize + 4;
+            let desc = page.get(offset..offset + desc_len).ok_or_else(truncated)?;
+            if desc_len < 40 {
+                return Err(invalid_data(format!(
+                    "enclosure descriptor too short: {} bytes",
+                    desc_len
+                )));
+            }
+            type_headers += desc[2] as usize;
+            enclosures.push(EnclosureDescriptor {
+                subenclosure_id: desc[1],
+                logical_id: be64(&desc[4..12]),
+                vendor: ascii(&desc[12..20]),
+                product: ascii(&desc[20..36]),
+                revision: ascii(&desc[36..40]),
+            });
+            offset += desc_len;
+        }
+
+        let headers = page
+            .get(offset..offset + type_headers * 4)
+            .ok_or_else(truncated)?;
+        let mut text_offset = offset + type_headers * 4;
+        let mut types = Vec::new();
+        for header in headers.chunks(4) {
+            let text_len = header[3] as usize;
+            let text = page
+                .get(text_offset..text_offset + text_len)
+                .map(ascii)
+                .unwrap_or_default();
+            text_offset += text_len;
+            types.push(TypeDescriptor {
+                element_type: ElementType::from(header[0]),
+                possible_elements: header[1],
+                subenclosure_id: header[2],
+                text,
+            });
+        }
+
+        Ok(Configuration {
+            generation,
+            enclosures,
+            types,
+        })
+    }
+
+    /// Returns the decoded enclosure status diagnostic page.
+    ///
+    /// Every type descriptor header is followed by its overall status element
+    /// and one status element per possible element, the element descriptors
+    /// are left empty.
+    ///
+    /// # Arguments
+    ///
+    /// * `page` - the raw page as returned by RECEIVE DIAGNOSTIC RESULTS
+    /// * `config` - the configuration page describing the elements
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::Ses::{decode_enclosure_status, Configuration, ElementStatusCode,
+    ///     ElementType, TypeDescriptor};
+    ///
+    /// let config = Configuration {
+    ///     generation: 1,
+    ///     enclosures: Vec::new(),
+    ///     types: vec![TypeDescriptor {
+    ///         element_type: ElementType::Cooling,
+    ///         possible_elements: 2,
+    ///         subenclosure_id: 0,
+    ///         text: "Fans".to_string(),
+    ///     }],
+    /// };
+    /// let page = [
+    ///     0x02, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x01,
+    ///     0x00, 0x00, 0x00, 0x00,
+    ///     0x01, 0x03, 0xbe, 0x23,
+    ///     0x05, 0x00, 0x00, 0x00,
+    /// ];
+    /// let status = decode_enclosure_status(&page, &config).unwrap();
+    ///
+    /// assert_eq!(status.elements.len(), 3);
+    /// assert_eq!(status.elements[1].sg_ses_index(), "0,0");
+    /// assert_eq!(status.elements[1].status_code(), ElementStatusCode::Ok);
+    /// assert_eq!(status.elements[2].status_code(), ElementStatusCode::NotInstalled);
+    /// ```
+    ///
+    pub fn decode_enclosure_status(page: &[u8], config: &Configuration) -> io::Result<EnclosureStatus> {
+        let page = check_page(page, ENCLOSURE_STATUS_PAGE)?;
+        let generation = be32(&page[4..8]);
+        if generation != config.generation {
+            return Err(invalid_data(format!(
+                "generation code changed from {} to {}",
+                config.generation, generation
+            )));
+        }
+
+        let mut elements = Vec::new();
+        let mut offset = 8;
+        for (type_index, type_desc) in config.types.iter().enumerate() {
+            for index in 0..=type_desc.possible_elements as usize {
+                let raw = page.get(offset..offset + 4).ok_or_else(|| {
+                    invalid_data("enclosure status page truncated".to_string())
+                })?;
+                elements.push(Element {
+                    element_type: type_desc.element_type,
+                    type_index,
+                    index: index.checked_sub(1),
+                    subenclosure_id: type_desc.subenclosure_id,
+                    descriptor: String::new(),
+                    status: [raw[0], raw[1], raw[2], raw[3]],
+                });
+                offset += 4;
+            }
+        }
+
+        Ok(EnclosureStatus {
+            generation,
+            invop: page[1] & 0x10 != 0,
+            info: page[1] & 0x08 != 0,
+            non_critical: page[1] & 0x04 != 0,
+            critical: page[1] & 0x02 != 0,
+            unrecoverable: page[1] & 0x01 != 0,
+            elements,
+        })
+    }
+
+    /// Returns the text of every element descriptor, in the same order as the
+    /// elements of `decode_enclosure_status`.
+    ///
+    /// # Arguments
+    ///
+    /// * `page` - the raw page as returned by RECEIVE DIAGNOSTIC RESULTS
+    /// * `config` - the configuration page describing the elements
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::Ses::{decode_element_descriptors, Configuration, ElementType,
+    ///     TypeDescriptor};
+    ///
+    /// let config = Configuration {
+    ///     generation: 1,
+    ///     enclosures: Vec::new(),
+    ///     types: vec![TypeDescriptor {
+    ///         element_type: ElementType::Cooling,
+    ///         possible_elements: 1,
+    ///         subenclosure_id: 0,
+    ///         text: "Fans".to_string(),
+    ///     }],
+    /// };
+    /// let page = [
+    ///     &[0x07, 0x00, 0x00, 0x15, 0x00, 0x00, 0x00, 0x01][..],
+    ///     &[0x00, 0x00, 0x00, 0x04], b"Fans",
+    ///     &[0x00, 0x00, 0x00, 0x05], b"Fan 1",
+    /// ]
+    /// .concat();
+    /// let descriptors = decode_element_descriptors(&page, &config).unwrap();
+    ///
+    /// assert_eq!(descriptors, vec!["Fans", "Fan 1"]);
+    /// ```
+    ///
+    pub fn decode_element_descriptors(page: &[u8], config: &Configuration) -> io::Result<Vec<String>> {
+        let page = check_page(page, ELEMENT_DESCRIPTOR_PAGE)?;
+        let truncated = || invalid_data("element descriptor page truncated".to_string());
+        let mut descriptors = Vec::new();
+        let mut offset = 8;
+        for type_desc in config.types.iter() {
+            for _ in 0..=type_desc.possible_elements {
+                let header = page.get(offset..offset + 4).ok_or_else(truncated)?;
+                let len = be16(&header[2..4]) as usize;
+                let text = page.get(offset + 4..offset + 4 + len).ok_or_else(truncated)?;
+                descriptors.push(ascii(text));
+                offset += 4 + len;
+            }
+        }
+
+        Ok(descriptors)
+    }
+
+    /// Returns the SAS additional element status of every device slot.
+    ///
+    /// Only the descriptors carrying their element index (EIP) are decoded,
+    /// the other ones cannot be matched to an element reliably and are
+    /// skipped, as well as the descriptors of other protocols.
+    ///
+    /// # Arguments
+    ///
+    /// * `page` - the raw page as returned by RECEIVE DIAGNOSTIC RESULTS
+    /// * `config` - the configuration page describing the elements
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::Ses::{decode_additional_element_status, Configuration, ElementType,
+    ///     TypeDescriptor};
+    ///
+    /// let config = Configuration {
+    ///     generation: 1,
+    ///     enclosures: Vec::new(),
+    ///     types: vec![TypeDescriptor {
+    ///         element_type: ElementType::ArrayDeviceSlot,
+    ///         possible_elements: 1,
+    ///         subenclosure_id: 0,
+    ///         text: "Disks".to_string(),
+    ///     }],
+    /// };
+    /// let page = [
+    ///     &[0x0a, 0x00, 0x00, 0x28, 0x00, 0x00, 0x00, 0x01][..],
+    ///     &[0x16, 0x22, 0x00, 0x00, 0x01, 0x00, 0x00, 0x05],
+    ///     &[0x00; 12],
+    ///     &[0x50, 0x00, 0xcc, 0xa2, 0x6b, 0x1a, 0xc3, 0xa9],
+    ///     &[0x00; 8],
+    /// ]
+    /// .concat();
+    /// let statuses = decode_additional_element_status(&page, &config).unwrap();
+    ///
+    /// assert_eq!(statuses[0].element, 1);
+    /// assert_eq!(statuses[0].device_slot, 5);
+    /// assert_eq!(statuses[0].sas_addresses, vec![0x5000cca26b1ac3a9]);
+    /// ```
+    ///
+    pub fn decode_additional_element_status(
+        page: &[u8],
+        config: &Configuration,
+    ) -> io::Result<Vec<AdditionalStatus>> {
+        let page = check_page(page, ADDITIONAL_ELEMENT_STATUS_PAGE)?;
+        let generation = be32(&page[4..8]);
+        if generation != config.generation {
+            return Err(invalid_data(format!(
+                "generation code changed from {} to {}",
+                config.generation, generation
+            )));
+        }
+
+        // The element index skips the overall elements unless EIIOE is set.
+        let mut individual = Vec::new();
+        let mut position = 0;
+        for type_desc in config.types.iter() {
+            position += 1;
+            for _ in 0..type_desc.possible_elements {
+                individual.push(position);
+                position += 1;
+            }
+        }
+
+        let mut statuses = Vec::new();
+        let mut offset = 8;
+        while offset + 2 <= page.len() {
+            let len = page[offset + 1] as usize + 2;
+            let desc = page.get(offset..offset + len).ok_or_else(|| {
+                invalid_data("additional element status page truncated".to_string())
+            })?;
+            offset += len;
+
+            let invalid = desc[0] & 0x80 != 0;
+            let eip = desc[0] & 0x10 != 0;
+            if invalid || !eip || desc[0] & 0x0f != SAS_PROTOCOL || desc.len() < 8 {
+                continue;
+            }
+            let index = desc[3] as usize;
+            let element = match desc[2] & 0x01 {
+                0 => individual.get(index).copied(),
+                _ => Some(index),
+            };
+            let Some(element) = element else {
+                continue;
+            };
+
+            // The descriptor type 0 is the one of the device slots.
+            let info = &desc[4..];
+            if info[1] >> 6 != 0 {
+                continue;
+            }
+            let sas_addresses = (0..info[0] as usize)
+                .filter_map(|phy| {
+                    let start = 4 + phy * SAS_PHY_DESCRIPTOR_LEN + 12;
+                    info.get(start..start + 8)
+                })
+                .map(be64)
+                .collect();
+            statuses.push(AdditionalStatus {
+                element,
+                device_slot: info[3],
+                sas_addresses,
+            });
+        }
+
+        Ok(statuses)
+    }
+
+    /// Returns a diagnostic page read with RECEIVE DIAGNOSTIC RESULTS.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to send the command
+    /// * `device` - the enclosure device, example: /dev/sg9
+    /// * `page_code` - the diagnostic page we want
+    ///
+    pub fn receive_diagnostic(ctx: &Context, device: &str, page_code: u8) -> io::Result<Vec<u8>> {
+        let cdb = [
+            RECEIVE_DIAGNOSTIC_RESULTS,
+            0x01, // PCV, the page code is valid
+            page_code,
+            (MAX_PAGE_LEN >> 8) as u8,
+            (MAX_PAGE_LEN & 0xff) as u8,
+            0x00,
+        ];
+        let page = ctx.scsi_in(device, &cdb, MAX_PAGE_LEN)?;
+        Ok(check_page(&page, page_code)?.to_vec())
+    }
+
+    /// Returns the SAS additional element status of every device slot of an
+    /// enclosure.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to send the commands
+    /// * `device` - the enclosure device, example: /dev/sg9
+    ///
+    pub fn read_additional_element_status(
+        ctx: &Context,
+        device: &str,
+    ) -> io::Result<Vec<AdditionalStatus>> {
+        let config = read_configuration(ctx, device)?;
+        let page = receive_diagnostic(ctx, device, ADDITIONAL_ELEMENT_STATUS_PAGE)?;
+        decode_additional_element_status(&page, &config)
+    }
+
+    /// Sends a diagnostic page with SEND DIAGNOSTIC.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to send the command
+    /// * `device` - the enclosure device, example: /dev/sg9
+    /// * `page` - the diagnostic page, example: the output of `encode_enclosure_control`
+    ///
+    pub fn send_diagnostic(ctx: &Context, device: &str, page: &[u8]) -> io::Result<()> {
+        if page.len() > MAX_PAGE_LEN {
+            return Err(invalid_data(format!("page too long: {} bytes", page.len())));
+        }
+        let cdb = [
+            SEND_DIAGNOSTIC,
+            0x10, // PF, the data is a diagnostic page
+            0x00,
+            (page.len() >> 8) as u8,
+            (page.len() & 0xff) as u8,
+            0x00,
+        ];
+        ctx.scsi_out(device, &cdb, page)
+    }
+
+    /// Returns the enclosure control page (0x02) selecting the given elements.
+    ///
+    /// Every element not listed in `controls` is left unselected, so the
+    /// enclosure ignores it. The generation code of `status` is sent back,
+    /// the enclosure rejects the page if its configuration changed since.
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - the enclosure status the control page is built from
+    /// * `controls` - the position of the element in `status.elements` and
+    ///   its control bytes, with the SELECT bit set
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::Ses::{encode_enclosure_control, Element, ElementType, EnclosureStatus};
+    ///
+    /// let fan = |index| Element {
+    ///     element_type: ElementType::Cooling,
+    ///     type_index: 0,
+    ///     index,
+    ///     subenclosure_id: 0,
+    ///     descriptor: String::new(),
+    ///     status: [0x01, 0x03, 0xbe, 0x23],
+    /// };
+    /// let status = EnclosureStatus {
+    ///     generation: 7,
+    ///     invop: false,
+    ///     info: false,
+    ///     non_critical: false,
+    ///     critical: false,
+    ///     unrecoverable: false,
+    ///     elements: vec![fan(None), fan(Some(0))],
+    /// };
+    /// let page = encode_enclosure_control(&status, &[(1, [0x80, 0x00, 0x00, 0x25])]);
+    ///
+    /// assert_eq!(page, vec![
+    ///     0x02, 0x00, 0x00, 0x0c, 0x00, 0x00, 0x00, 0x07,
+    ///     0x00, 0x00, 0x00, 0x00,
+    ///     0x80, 0x00, 0x00, 0x25,
+    /// ]);
+    /// ```
+    ///
+    pub fn encode_enclosure_control(status: &EnclosureStatus, controls: &[(usize, [u8; 4])]) -> Vec<u8> {
+        let len = 4 + 4 * status.elements.len();
+        let mut page = vec![0u8; 4 + len];
+        page[0] = ENCLOSURE_CONTROL_PAGE;
+        page[2..4].copy_from_slice(&(len as u16).to_be_bytes());
+        page[4..8].copy_from_slice(&status.generation.to_be_bytes());
+        for (position, control) in controls {
+            let offset = 8 + 4 * position;
+            if let Some(element) = page.get_mut(offset..offset + 4) {
+                element.copy_from_slice(control);
+            }
+        }
+        page
+    }
+
+    /// Returns the bytes of a page as `sg_ses --hex` prints them, 16 bytes
+    /// per line prefixed by their offset.
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::Ses::hex_dump;
+    ///
+    /// assert_eq!(hex_dump(&[0x02, 0x00, 0x00, 0x0c]), "  00     02 00 00 0c");
+    /// ```
+    pub fn hex_dump(bytes: &[u8]) -> String {
+        bytes
+            .chunks(16)
+            .enumerate()
+            .map(|(line, chunk)| {
+                let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+                format!("  {:02x}     {}", line * 16, hex.join(" "))
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Returns the configuration page of an enclosure.
+    pub fn read_configuration(ctx: &Context, device: &str) -> io::Result<Configuration> {
+        decode_configuration(&receive_diagnostic(ctx, device, CONFIGURATION_PAGE)?)
+    }
+
+    /// Returns the enclosure status with the element descriptors filled in.
+    ///
+    /// Not every enclosure provides the element descriptor page, in that case
+    /// the descriptors are left empty.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to send the commands
+    /// * `device` - the enclosure device, example: /dev/sg9
+    ///
+    pub fn read_enclosure_status(ctx: &Context, device: &str) -> io::Result<EnclosureStatus> {
+        let config = read_configuration(ctx, device)?;
+        read_status_with(ctx, device, &config)
+    }
+
+    /// Returns the enclosure status and the additional element status of
+    /// an enclosure, the configuration page is read only once.
+    ///
+    /// Not every enclosure provides the element descriptor and additional
+    /// element status pages, in that case they are left empty.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context used to send the commands
+    /// * `device` - the enclosure device, example: /dev/sg9
+    ///
+    pub fn read_enclosure_pages(ctx: &Context, device: &str) -> io::Result<EnclosurePages> {
+        let config = read_configuration(ctx, device)?;
+        let status = read_status_with(ctx, device, &config)?;
+        let additional = receive_diagnostic(ctx, device, ADDITIONAL_ELEMENT_STATUS_PAGE)
+            .and_then(|page| decode_additional_element_status(&page, &config))
+            .unwrap_or_default();
+
+        Ok(EnclosurePages { status, additional })
+    }
+
+    /// Returns the enclosure status of an enclosure whose configuration
+    /// page was already read, with the element descriptors filled in.
+    fn read_status_with(
+        ctx: &Context,
+        device: &str,
+        config: &Configuration,
+    ) -> io::Result<EnclosureStatus> {
+        let page = receive_diagnostic(ctx, device, ENCLOSURE_STATUS_PAGE)?;
+        let mut status = decode_enclosure_status(&page, config)?;
+
+        let descriptors = receive_diagnostic(ctx, device, ELEMENT_DESCRIPTOR_PAGE)
+            .and_then(|page| decode_element_descriptors(&page, config))
+            .unwrap_or_default();
+        for (element, descriptor) in status.elements.iter_mut().zip(descriptors) {
+            element.descriptor = descriptor;
+        }
+
+        Ok(status)
+    }
+
+    /// Returns the actual speed of a cooling element in RPM.
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::Ses::fan_speed;
+    ///
+    /// assert_eq!(fan_speed(&[0x01, 0x03, 0xbe, 0x23]), 9580);
+    /// ```
+    pub fn fan_speed(status: &[u8; 4]) -> i64 {
+        let speed = (((status[1] & 0x07) as i64) << 8) | status[2] as i64;
+        match (status[1] >> 3) & 0x03 {
+            0 => speed * 10,
+            1 => speed * 10 + 20480,
+            2 => speed * 100,
+            _ => 0,
+        }
+    }
+
+    /// Returns the actual speed code of a cooling element, from 0 (stopped) to 7 (highest).
+    pub fn fan_speed_code(status: &[u8; 4]) -> u8 {
+        status[3] & 0x07
+    }
+
+    /// Returns the description of a cooling element speed code, example: "at lowest speed".
+    pub fn fan_speed_code_desc(code: u8) -> &'static str {
+        FAN_SPEED_CODE_DESC[(code & 0x07) as usize]
+    }
+
+    /// Returns the control bytes of a cooling element asking for `speed_code`.
+    ///
+    /// The element is selected and requested on, the ident led of the
+    /// element is left as reported in its `status`.
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::Ses::cooling_control;
+    ///
+    /// assert_eq!(cooling_control(&[0x01, 0x83, 0xbe, 0x27], 3), [0x80, 0x80, 0x00, 0x23]);
+    /// ```
+    pub fn cooling_control(status: &[u8; 4], speed_code: u8) -> [u8; 4] {
+        [0x80, status[1] & 0x80, 0x00, 0x20 | (speed_code & 0x07)]
+    }
+
+    /// The state bits of a cooling element, the speed is decoded by
+    /// `fan_speed` and `fan_speed_code`.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+    pub struct CoolingStatus {
+        pub ident: bool,
+        pub do_not_remove: bool,
+        pub hot_swap: bool,
+        pub fail: bool,
+        pub requested_on: bool,
+        pub off: bool,
+    }
+
+    impl CoolingStatus {
+        /// Returns the name of every flag set, example: ["fail", "off"].
+        pub fn flags(&self) -> Vec<&'static str> {
+            [
+                (self.fail, "fail"),
+                (self.requested_on, "requested on"),
+                (self.off, "off"),
+                (self.hot_swap, "hot swap"),
+                (self.ident, "ident"),
+                (self.do_not_remove, "do not remove"),
+            ]
+            .iter()
+            .filter(|(set, _)| *set)
+            .map(|(_, name)| *name)
+            .collect()
+        }
+    }
+
+    /// Returns the decoded state bits of a cooling element.
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::Ses::decode_cooling;
+    ///
+    /// let fan = decode_cooling(&[0x01, 0x03, 0xbe, 0x23]);
+    ///
+    /// assert!(fan.requested_on && !fan.off && !fan.fail);
+    /// ```
+    pub fn decode_cooling(status: &[u8; 4]) -> CoolingStatus {
+        CoolingStatus {
+            ident: status[1] & 0x80 != 0,
+            do_not_remove: status[1] & 0x40 != 0,
+            hot_swap: status[3] & 0x80 != 0,
+            fail: status[3] & 0x40 != 0,
+            requested_on: status[3] & 0x20 != 0,
+            off: status[3] & 0x10 != 0,
+        }
+    }
+
+    /// The status of a power supply element.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+    pub struct PowerSupplyStatus {
+        pub ident: bool,
+        pub do_not_remove: bool,
+        pub dc_overvoltage: bool,
+        pub dc_undervoltage: bool,
+        pub dc_overcurrent: bool,
+        pub hot_swap: bool,
+        pub fail: bool,
+        pub requested_on: bool,
+        pub off: bool,
+        pub overtemp_failure: bool,
+        pub overtemp_warning: bool,
+        pub ac_failure: bool,
+        pub dc_failure: bool,
+    }
+
+    impl PowerSupplyStatus {
+        /// Returns the name of every fault or state flag set, example: ["AC fail", "off"].
+        pub fn flags(&self) -> Vec<&'static str> {
+            [
+                (self.dc_overvoltage, "DC overvoltage"),
+                (self.dc_undervoltage, "DC undervoltage"),
+                (self.dc_overcurrent, "DC overcurrent"),
+                (self.fail, "fail"),
+                (self.overtemp_failure, "overtemp fail"),
+                (self.overtemp_warning, "overtemp warn"),
+                (self.ac_failure, "AC fail"),
+                (self.dc_failure, "DC fail"),
+                (self.off, "off"),
+                (self.ident, "ident"),
+                (self.do_not_remove, "do not remove"),
+            ]
+            .iter()
+            .filter(|(set, _)| *set)
+            .map(|(_, name)| *name)
+            .collect()
+        }
+    }
+
+    /// Returns the decoded status of a power supply element.
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::Ses::decode_power_supply;
+    ///
+    /// let psu = decode_power_supply(&[0x02, 0x00, 0x00, 0x62]);
+    ///
+    /// assert!(psu.fail && psu.requested_on && psu.ac_failure);
+    /// assert_eq!(psu.flags(), vec!["fail", "AC fail"]);
+    /// ```
+    pub fn decode_power_supply(status: &[u8; 4]) -> PowerSupplyStatus {
+        PowerSupplyStatus {
+            ident: status[1] & 0x80 != 0,
+            do_not_remove: status[1] & 0x40 != 0,
+            dc_overvoltage: status[2] & 0x08 != 0,
+            dc_undervoltage: status[2] & 0x04 != 0,
+            dc_overcurrent: status[2] & 0x02 != 0,
+            hot_swap: status[3] & 0x80 != 0,
+            fail: status[3] & 0x40 != 0,
+            requested_on: status[3] & 0x20 != 0,
+            off: status[3] & 0x10 != 0,
+            overtemp_failure: status[3] & 0x08 != 0,
+            overtemp_warning: status[3] & 0x04 != 0,
+            ac_failure: status[3] & 0x02 != 0,
+            dc_failure: status[3] & 0x01 != 0,
+        }
+    }
+
+    /// The failure and threshold bits shared by the sensor elements, a
+    /// temperature failure is reported as critical.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+    pub struct SensorStatus {
+        pub ident: bool,
+        pub fail: bool,
+        pub over_critical: bool,
+        pub over_warning: bool,
+        pub under_critical: bool,
+        pub under_warning: bool,
+    }
+
+    impl SensorStatus {
+        /// Returns the name of every flag set, example: ["over warning"].
+        pub fn flags(&self) -> Vec<&'static str> {
+            [
+                (self.fail, "fail"),
+                (self.over_critical, "over critical"),
+                (self.over_warning, "over warning"),
+                (self.under_critical, "under critical"),
+                (self.under_warning, "under warning"),
+                (self.ident, "ident"),
+            ]
+            .iter()
+            .filter(|(set, _)| *set)
+            .map(|(_, name)| *name)
+            .collect()
+        }
+    }
+
+    /// A decoded temperature sensor element.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct TemperatureSensor {
+        /// The temperature in Celsius, `None` if the sensor has no reading.
+        pub temperature: Option<i32>,
+        pub flags: SensorStatus,
+    }
+
+    /// Returns the decoded status of a temperature sensor element.
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::Ses::decode_temperature_sensor;
+    ///
+    /// let sensor = decode_temperature_sensor(&[0x01, 0x00, 0x2d, 0x04]);
+    ///
+    /// assert_eq!(sensor.temperature, Some(25));
+    /// assert!(sensor.flags.over_warning && !sensor.flags.over_critical);
+    /// assert_eq!(decode_temperature_sensor(&[0x01, 0x00, 0x00, 0x00]).temperature, None);
+    /// ```
+    pub fn decode_temperature_sensor(status: &[u8; 4]) -> TemperatureSensor {
+        TemperatureSensor {
+            // The value is offset by 20 so it can go down to -19, 0 is reserved.
+            temperature: match status[2] {
+                0 => None,
+                value => Some(value as i32 - 20),
+            },
+            flags: SensorStatus {
+                ident: status[1] & 0x80 != 0,
+                fail: status[1] & 0x40 != 0,
+                over_critical: status[3] & 0x08 != 0,
+                over_warning: status[3] & 0x04 != 0,
+                under_critical: status[3] & 0x02 != 0,
+                under_warning: status[3] & 0x01 != 0,
+            },
+        }
+    }
+
+    /// A decoded voltage or current sensor element.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct ElectricalSensor {
+        /// The reading in volts or amps.
+        pub value: f64,
+        pub flags: SensorStatus,
+    }
+
+    /// Returns the decoded status of a voltage sensor element, the voltage
+    /// is reported in units of 10 mV and can be negative.
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::Ses::decode_voltage_sensor;
+    ///
+    /// let sensor = decode_voltage_sensor(&[0x01, 0x08, 0x04, 0xec]);
+    ///
+    /// assert_eq!(sensor.value, 12.6);
+    /// assert!(sensor.flags.over_warning && !sensor.flags.under_critical);
+    /// ```
+    pub fn decode_voltage_sensor(status: &[u8; 4]) -> ElectricalSensor {
+        ElectricalSensor {
+            value: i16::from_be_bytes([status[2], status[3]]) as f64 / 100.0,
+            flags: SensorStatus {
+                ident: status[1] & 0x80 != 0,
+                fail: status[1] & 0x40 != 0,
+                over_warning: status[1] & 0x08 != 0,
+                under_warning: status[1] & 0x04 != 0,
+                over_critical: status[1] & 0x02 != 0,
+                under_critical: status[1] & 0x01 != 0,
+            },
+        }
+    }
+
+    /// Returns the decoded status of a current sensor element, the current
+    /// is reported in units of 10 mA.
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::Ses::decode_current_sensor;
+    ///
+    /// let sensor = decode_current_sensor(&[0x02, 0x02, 0x03, 0xe8]);
+    ///
+    /// assert_eq!(sensor.value, 10.0);
+    /// assert!(sensor.flags.over_critical);
+    /// ```
+    pub fn decode_current_sensor(status: &[u8; 4]) -> ElectricalSensor {
+        ElectricalSensor {
+            value: be16(&status[2..4]) as f64 / 100.0,
+            flags: SensorStatus {
+                ident: status[1] & 0x80 != 0,
+                fail: status[1] & 0x40 != 0,
+                over_warning: status[1] & 0x08 != 0,
+                over_critical: status[1] & 0x02 != 0,
+                ..SensorStatus::default()
+            },
+        }
+    }
+
+    /// The state bits of a device slot or an array device slot element.
+    ///
+    /// The array bits (hot spare, rebuild, in critical array, ...) are set
+    /// by the RAID controller and are always false on a device slot.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+    pub struct SlotStatus {
+        pub swap: bool,
+        pub predicted_failure: bool,
+        pub disabled: bool,
+        pub hot_spare: bool,
+        pub consistency_check: bool,
+        pub in_critical_array: bool,
+        pub in_failed_array: bool,
+        pub rebuild_remap: bool,
+        pub rebuild_abort: bool,
+        pub device_off: bool,
+        /// The device or the enclosure bypassed one of the device ports.
+        pub bypassed: bool,
+        pub fault_sensed: bool,
+        pub ident: bool,
+    }
+
+    impl SlotStatus {
+        /// Returns every flag with its name, the same as the field name,
+        /// example: ("predicted_failure", false).
+        pub fn states(&self) -> Vec<(&'static str, bool)> {
+            vec![
+                ("swap", self.swap),
+                ("predicted_failure", self.predicted_failure),
+                ("disabled", self.disabled),
+                ("hot_spare", self.hot_spare),
+                ("consistency_check", self.consistency_check),
+                ("in_critical_array", self.in_critical_array),
+                ("in_failed_array", self.in_failed_array),
+                ("rebuild_remap", self.rebuild_remap),
+                ("rebuild_abort", self.rebuild_abort),
+                ("device_off", self.device_off),
+                ("bypassed", self.bypassed),
+                ("fault_sensed", self.fault_sensed),
+                ("ident", self.ident),
+            ]
+        }
+
+        /// Returns the name of every flag set, example: ["swap", "hot_spare"].
+        pub fn flags(&self) -> Vec<&'static str> {
+            self.states()
+                .into_iter()
+                .filter(|(_, set)| *set)
+                .map(|(name, _)| name)
+                .collect()
+        }
+    }
+
+    /// Returns the decoded state bits of a device slot or an array device
+    /// slot element.
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::Ses::decode_slot;
+    ///
+    /// let slot = decode_slot(&[0x51, 0x22, 0x00, 0x00]);
+    ///
+    /// assert_eq!(slot.flags(), vec!["swap", "predicted_failure", "hot_spare", "rebuild_remap"]);
+    /// ```
+    pub fn decode_slot(status: &[u8; 4]) -> SlotStatus {
+        SlotStatus {
+            swap: status[0] & 0x10 != 0,
+            predicted_failure: status[0] & 0x40 != 0,
+            disabled: status[0] & 0x20 != 0,
+            hot_spare: status[1] & 0x20 != 0,
+            consistency_check: status[1] & 0x10 != 0,
+            in_critical_array: status[1] & 0x08 != 0,
+            in_failed_array: status[1] & 0x04 != 0,
+            rebuild_remap: status[1] & 0x02 != 0,
+            rebuild_abort: status[1] & 0x01 != 0,
+            device_off: status[3] & 0x10 != 0,
+            bypassed: status[3] & 0x0f != 0,
+            fault_sensed: status[3] & 0x40 != 0,
+            ident: status[2] & 0x02 != 0,
+        }
+    }
+
+    /// Returns the control bytes of an array device slot element requesting
+    /// the hot spare, in critical array, in failed array and rebuild/remap
+    /// bits of `requested`.
+    ///
+    /// The other requests are left as reported in its `status`: the
+    /// predicted failure and disable bits of byte 0, the OK, reserved
+    /// device, consistency check and rebuild/remap abort bits of byte 1,
+    /// the do not remove, insert, remove and ident bits of byte 2 and the
+    /// fault and device off bits of byte 3.
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::Ses::{array_slot_control, SlotStatus};
+    ///
+    /// let requested = SlotStatus { rebuild_remap: true, ..SlotStatus::default() };
+    ///
+    /// assert_eq!(array_slot_control(&[0x01, 0x88, 0x02, 0x00], &requested), [0x80, 0x82, 0x02, 0x00]);
+    /// // The reserved device bit is kept.
+    /// assert_eq!(array_slot_control(&[0x01, 0x40, 0x00, 0x00], &requested), [0x80, 0x42, 0x00, 0x00]);
+    /// // The predicted failure and disable bits are kept.
+    /// assert_eq!(array_slot_control(&[0x41, 0x00, 0x00, 0x00], &requested), [0xc0, 0x02, 0x00, 0x00]);
+    /// assert_eq!(array_slot_control(&[0x21, 0x00, 0x00, 0x00], &requested), [0xa0, 0x02, 0x00, 0x00]);
+    /// ```
+    pub fn array_slot_control(status: &[u8; 4], requested: &SlotStatus) -> [u8; 4] {
+        let mut array = status[1] & 0xd1;
+        for (set, bit) in [
+            (requested.hot_spare, 0x20),
+            (requested.in_critical_array, 0x08),
+            (requested.in_failed_array, 0x04),
+            (requested.rebuild_remap, 0x02),
+        ] {
+            if set {
+                array |= bit;
+            }
+        }
+        [0x80 | (status[0] & 0x60), array, status[2] & 0x4e, status[3] & 0x30]
+    }
+
+    /// The state and request bits of an audible alarm element.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+    pub struct AlarmStatus {
+        pub ident: bool,
+        pub fail: bool,
+        pub requested_mute: bool,
+        pub muted: bool,
+        pub remind: bool,
+        pub info: bool,
+        pub non_critical: bool,
+        pub critical: bool,
+        pub unrecoverable: bool,
+    }
+
+    impl AlarmStatus {
+        /// Returns the name of every flag set, example: ["muted", "critical"].
+        pub fn flags(&self) -> Vec<&'static str> {
+            [
+                (self.fail, "fail"),
+                (self.unrecoverable, "unrecoverable"),
+                (self.critical, "critical"),
+                (self.non_critical, "non-critical"),
+                (self.info, "info"),
+                (self.muted, "muted"),
+                (self.requested_mute, "mute requested"),
+                (self.remind, "remind"),
+                (self.ident, "ident"),
+            ]
+            .iter()
+            .filter(|(set, _)| *set)
+            .map(|(_, name)| *name)
+            .collect()
+        }
+    }
+
+    /// Returns the decoded status of an audible alarm element, the
+    /// unrecoverable, critical, non-critical and info bits tell why the
+    /// alarm sounds.
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::Ses::decode_audible_alarm;
+    ///
+    /// let alarm = decode_audible_alarm(&[0x02, 0x00, 0x00, 0x42]);
+    ///
+    /// assert!(alarm.muted && alarm.critical);
+    /// assert_eq!(alarm.flags(), vec!["critical", "muted"]);
+    /// ```
+    pub fn decode_audible_alarm(status: &[u8; 4]) -> AlarmStatus {
+        AlarmStatus {
+            ident: status[1] & 0x80 != 0,
+            fail: status[1] & 0x40 != 0,
+            requested_mute: status[3] & 0x80 != 0,
+            muted: status[3] & 0x40 != 0,
+            remind: status[3] & 0x10 != 0,
+            info: status[3] & 0x08 != 0,
+            non_critical: status[3] & 0x04 != 0,
+            critical: status[3] & 0x02 != 0,
+            unrecoverable: status[3] & 0x01 != 0,
+        }
+    }
+
+    /// Returns the control bytes of an audible alarm element muting or
+    /// unmuting it.
+    ///
+    /// The ident, remind and tone urgency bits are left as reported in its
+    /// `status`, so only the mute changes.
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::Ses::alarm_control;
+    ///
+    /// assert_eq!(alarm_control(&[0x02, 0x00, 0x00, 0x12], true), [0x80, 0x00, 0x00, 0x52]);
+    /// assert_eq!(alarm_control(&[0x02, 0x00, 0x00, 0x42], false), [0x80, 0x00, 0x00, 0x02]);
+    /// ```
+    pub fn alarm_control(status: &[u8; 4], mute: bool) -> [u8; 4] {
+        let mute = if mute { 0x40 } else { 0x00 };
+        [0x80, status[1] & 0x80, 0x00, mute | (status[3] & 0x1f)]
+    }
+}
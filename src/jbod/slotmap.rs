@@ -0,0 +1,314 @@
+/*-
+ * SPDX-License-Identifier: BSD-2-Clause
+ *
+ * BSD 2-Clause License
+ *
+ * Copyright (c) 2021-2023, Gandi S.A.S.
+ * All rights reserved.
+ *
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice, this
+ *    list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+ * AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+ * IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+ * FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+ * DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+ * SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+ * CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+ * OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+#[allow(non_snake_case)]
+pub mod SlotMap {
+    use colored::*;
+    use std::fs;
+    use std::path::Path;
+
+    use crate::jbod::disks::DiskShelf::Disk;
+    use crate::jbod::enclosure::BackPlane::{BayState, Enclosure};
+
+    /// How the slots of an enclosure are laid out, the slots are numbered
+    /// from the top left cell, row after row.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Geometry {
+        /// The name printed above the grid, example: Ultrastar Data60
+        pub name: String,
+        pub rows: usize,
+        pub columns: usize,
+        /// The slot number of the top left cell.
+        pub first_slot: u32,
+    }
+
+    /// The known enclosures, keyed on the INQUIRY vendor and the beginning
+    /// of the model: vendor, model, name, rows, columns, first slot.
+    ///
+    /// Only chassis belong here, a SAS expander model like the LSI SAS2X36
+    /// ships in chassis of every size and gets the generic grid.
+    ///
+    /// There is no 4U24 front panel profile: the 4U24 chassis we know of
+    /// only report their expander, add one here once a 4U24 chassis reports
+    /// its own vendor and model.
+    const PROFILES: &[(&str, &str, &str, usize, usize, u32)] = &[
+        ("HGST", "H4060-J", "Ultrastar Data60 drawer", 5, 12, 0),
+        ("WDC", "H4060-J", "Ultrastar Data60 drawer", 5, 12, 0),
+        ("HGST", "H4102-J", "Ultrastar Data102 drawer", 6, 17, 0),
+        ("WDC", "H4102-J", "Ultrastar Data102 drawer", 6, 17, 0),
+    ];
+
+    /// The columns used for an enclosure we have no profile for.
+    const DEFAULT_COLUMNS: usize = 12;
+
+    /// The state of a slot, it decides the color of its cell.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum SlotState {
+        Empty,
+        /// The enclosure reports a failure of the device in the slot.
+        Failed,
+        /// The enclosure reports a device in the slot but the OS does not see it.
+        NotResponding,
+        /// A disk is present, its temperature is fine or unknown.
+        Present,
+        /// A disk is present and above 45 Celsius.
+        Warm,
+        /// A disk is present and above 50 Celsius.
+        Hot,
+        /// The fault led of the slot is on.
+        Fault,
+    }
+
+    /// Returns the geometry of an enclosure.
+    ///
+    /// Unknown enclosures get rows of 12 slots, enough rows to show every
+    /// disk we found and every slot the enclosure reports.
+    ///
+    /// # Arguments
+    ///
+    /// * `enclosure` - the enclosure, with its disks and slots
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::BackPlane::Enclosure;
+    /// use jbod::SlotMap;
+    ///
+    /// let enclosure = Enclosure {
+    ///     slot: "0:0:0:0".to_string(), device_path: "/dev/sg0".to_string(),
+    ///     vendor: "HGST".to_string(), model: "H4060-J".to_string(), revision: "2033".to_string(),
+    ///     ..Default::default()
+    /// };
+    /// let geometry = SlotMap::geometry(&enclosure);
+    ///
+    /// assert_eq!((geometry.rows, geometry.columns), (5, 12));
+    ///
+    /// let expander = Enclosure {
+    ///     vendor: "LSI".to_string(), model: "SAS2X36".to_string(),
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(SlotMap::geometry(&expander).name, "LSI SAS2X36");
+    /// ```
+    ///
+    pub fn geometry(enclosure: &Enclosure) -> Geometry {
+        let profile = PROFILES.iter().find(|(vendor, model, ..)| {
+            enclosure.vendor.trim().eq_ignore_ascii_case(vendor)
+                && enclosure.model.trim().starts_with(model)
+        });
+        if let Some((_, _, name, rows, columns, first_slot)) = profile {
+            return Geometry {
+                name: name.to_string(),
+                rows: *rows,
+                columns: *columns,
+                first_slot: *first_slot,
+            };
+        }
+
+        let numbers = || {
+            let slots = enclosure.slots.iter().map(|s| s.number);
+            enclosure.disks.iter().map(|d| d.slot).chain(slots)
+        };
+        let first_slot = numbers().min().unwrap_or(0);
+        let last_slot = numbers().max().unwrap_or(0);
+        let slots = (last_slot - first_slot) as usize + 1;
+        Geometry {
+            name: format!("{} {}", enclosure.vendor.trim(), enclosure.model.trim())
+                .trim()
+                .to_string(),
+            rows: slots.div_ceil(DEFAULT_COLUMNS),
+            columns: slots.min(DEFAULT_COLUMNS),
+            first_slot,
+        }
+    }
+
+    /// Returns true if the led file says the led is on.
+    fn led_is_on(path: &Path) -> bool {
+        fs::read_to_string(path)
+            .map(|value| value.trim() != "0")
+            .unwrap_or(false)
+    }
+
+    /// Returns the state of a slot from the disk plugged in it, if any, and
+    /// the state reported by the enclosure.
+    ///
+    /// A failed or not responding device is shown even when the OS has no
+    /// disk for it.
+    ///
+    /// # Arguments
+    ///
+    /// * `disk` - the disk of the slot, `None` if the OS sees no disk there
+    /// * `bay` - the slot state reported by the enclosure, `None` if unknown
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::BackPlane::BayState;
+    /// use jbod::SlotMap::{slot_state, SlotState};
+    ///
+    /// assert_eq!(slot_state(None, None), SlotState::Empty);
+    /// assert_eq!(slot_state(None, Some(BayState::Empty)), SlotState::Empty);
+    /// assert_eq!(slot_state(None, Some(BayState::NotResponding)), SlotState::NotResponding);
+    /// assert_eq!(slot_state(None, Some(BayState::PresentFailed)), SlotState::Failed);
+    /// ```
+    ///
+    pub fn slot_state(disk: Option<&Disk>, bay: Option<BayState>) -> SlotState {
+        match bay {
+            Some(BayState::PresentFailed) => return SlotState::Failed,
+            Some(BayState::NotResponding) => return SlotState::NotResponding,
+            _ => (),
+        }
+        let disk = match disk {
+            Some(disk) => disk,
+            None => return SlotState::Empty,
+        };
+
+        if disk.led_fault_path.as_deref().map(led_is_on).unwrap_or(false) {
+            return SlotState::Fault;
+        }
+        match disk.temperature {
+            Some(t) if t > 50 => SlotState::Hot,
+            Some(t) if t > 45 => SlotState::Warm,
+            _ => SlotState::Present,
+        }
+    }
+
+    /// Returns the text of a cell colored by the slot state.
+    fn paint(text: &str, state: SlotState) -> String {
+        match state {
+            SlotState::Empty => text.dimmed().to_string(),
+            SlotState::Failed => text.white().on_magenta().bold().to_string(),
+            SlotState::NotResponding => text.black().on_yellow().to_string(),
+            SlotState::Present => text.green().to_string(),
+            SlotState::Warm => text.yellow().bold().to_string(),
+            SlotState::Hot => text.red().bold().to_string(),
+            SlotState::Fault => text.white().on_red().bold().to_string(),
+        }
+    }
+
+    /// The characters used to draw the grid.
+    struct Borders {
+        horizontal: &'static str,
+        vertical: &'static str,
+        // left, middle and right of the top, middle and bottom lines.
+        top: [&'static str; 3],
+        middle: [&'static str; 3],
+        bottom: [&'static str; 3],
+    }
+
+    const UNICODE: Borders = Borders {
+        horizontal: "─",
+        vertical: "│",
+        top: ["┌", "┬", "┐"],
+        middle: ["├", "┼", "┤"],
+        bottom: ["└", "┴", "┘"],
+    };
+
+    const ASCII: Borders = Borders {
+        horizontal: "-",
+        vertical: "|",
+        top: ["+", "+", "+"],
+        middle: ["+", "+", "+"],
+        bottom: ["+", "+", "+"],
+    };
+
+    /// Returns a border line of the grid.
+    fn border_line(borders: &Borders, corners: &[&str; 3], columns: usize, width: usize) -> String {
+        let cell = borders.horizontal.repeat(width);
+        let cells = vec![cell; columns];
+        format!("{}{}{}\n", corners[0], cells.join(corners[1]), corners[2])
+    }
+
+    /// Returns the grid of an enclosure, one cell per slot with the slot
+    /// number colored by the slot state, followed by a legend.
+    ///
+    /// # Arguments
+    ///
+    /// * `enclosure` - the enclosure, with its disks and slots
+    /// * `unicode` - draw the grid with box-drawing characters instead of ASCII
+    ///
+    pub fn render(enclosure: &Enclosure, unicode: bool) -> String {
+        let borders = if unicode { &UNICODE } else { &ASCII };
+        let geometry = geometry(enclosure);
+        let last_slot = geometry.first_slot as usize + geometry.rows * geometry.columns;
+        let width = last_slot.to_string().len() + 2;
+
+        let mut output = format!(
+            "{} {} ({})\n",
+            enclosure.slot.bold(),
+            enclosure.serial.as_deref().unwrap_or("NONE"),
+            geometry.name
+        );
+        output.push_str(&border_line(borders, &borders.top, geometry.columns, width));
+        for row in 0..geometry.rows {
+            output.push_str(borders.vertical);
+            for column in 0..geometry.columns {
+                let slot = geometry.first_slot + (row * geometry.columns + column) as u32;
+                let disk = enclosure.disks.iter().find(|d| d.slot == slot);
+                let bay = enclosure.slots.iter().find(|s| s.number == slot).map(|s| s.state);
+                let text = format!("{:>w$} ", slot, w = width - 1);
+                output.push_str(&paint(&text, slot_state(disk, bay)));
+                output.push_str(borders.vertical);
+            }
+            output.push('\n');
+            let corners = if row + 1 == geometry.rows { &borders.bottom } else { &borders.middle };
+            output.push_str(&border_line(borders, corners, geometry.columns, width));
+        }
+
+        let mut outside: Vec<u32> = enclosure
+            .disks
+            .iter()
+            .map(|d| d.slot)
+            .chain(
+                enclosure
+                    .slots
+                    .iter()
+                    .filter(|s| s.state != BayState::Empty)
+                    .map(|s| s.number),
+            )
+            .filter(|&slot| slot < geometry.first_slot || slot as usize >= last_slot)
+            .collect();
+        outside.sort_unstable();
+        outside.dedup();
+        let outside: Vec<String> = outside.iter().map(u32::to_string).collect();
+        if !outside.is_empty() {
+            output.push_str(&format!("Slots outside of the grid: {}\n", outside.join(", ")));
+        }
+
+        output.push_str(&format!(
+            "{} {} {} {} {} {} {}\n",
+            paint("empty", SlotState::Empty),
+            paint("present", SlotState::Present),
+            paint(">45c", SlotState::Warm),
+            paint(">50c", SlotState::Hot),
+            paint("fault", SlotState::Fault),
+            paint("failed", SlotState::Failed),
+            paint("not-responding", SlotState::NotResponding),
+        ));
+        output
+    }
+}
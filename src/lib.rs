@@ -0,0 +1,92 @@
+/*-
+ * SPDX-License-Identifier: BSD-2-Clause
+ *
+ * BSD 2-Clause License
+ *
+ * Copyright (c) 2021-2023, Gandi S.A.S.
+ * All rights reserved.
+ *
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice, this
+ *    list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+ * AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+ * IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+ * FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+ * DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+ * SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+ * CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+ * OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+//! jbod - storage enclosure library
+//!
+//! This crate exposes the enclosure and disk discovery used by the `jbod`
+//! CLI and the `prometheus-jbod-exporter`, so other tools can query a JBOD
+//! without scraping the command line output.
+//!
+//! The public API is organized as:
+//!
+//! * [`BackPlane`] - enclosures, their FAN and power supplies (`get_enclosure`,
+//!   `get_enclosure_fan`, `get_enclosure_elements`), FAN speed control
+//!   (`set_fan_speed`), audible alarms (`get_enclosure_alarm`,
+//!   `set_alarm_mute`) and the array bits of the slots (`sync_slot_array_flags`).
+//! * [`DiskShelf`] - disks per enclosure and led control (`jbod_inventory`,
+//!   `jbod_disk_map`, `set_disk_led_locate`, `set_disk_led_fault`).
+//! * [`SlotMap`] - the physical slot grid of an enclosure model.
+//! * [`Ses`] - native SES diagnostic pages (configuration, enclosure status,
+//!   element descriptors) read over the SG_IO ioctl, and the enclosure
+//!   control page sent back with SEND DIAGNOSTIC.
+//! * [`Scsi`] - native INQUIRY, VPD pages and temperature log page for
+//!   enclosures and disks.
+//! * [`JbodError`] - the error returned by every probe, the library never
+//!   exits the process.
+//! * [`Output`] - the machine readable output of `jbod list`.
+//! * [`Template`] - the user defined output of `jbod list --format`.
+//! * [`MdStat`] - the md arrays of /proc/mdstat, used by `jbod led sync-raid`.
+//! * [`Util`] - helpers and the path of the external tools we rely on.
+//! * [`Context`] - the backend every probe runs through, `Context::default()`
+//!   runs the real tools while a `FixtureRunner` replays captured output.
+//!
+//! # Example
+//! ```no_run
+//! use jbod::{BackPlane, Context, DiskShelf};
+//!
+//! let ctx = Context::default();
+//! for enclosure in BackPlane::get_enclosure(&ctx)? {
+//!     println!("{} {:?}", enclosure.slot, enclosure.serial);
+//! }
+//! for enclosure in DiskShelf::jbod_inventory(&ctx)? {
+//!     for disk in &enclosure.disks {
+//!         println!("{} {} {:?}", enclosure.slot, disk.slot, disk.temperature);
+//!     }
+//! }
+//! for disk in DiskShelf::jbod_disk_map(&ctx)? {
+//!     println!("{} {} {}", disk.enclosure, disk.slot, disk.device_path);
+//! }
+//! # Ok::<(), jbod::JbodError>(())
+//! ```
+
+pub mod jbod;
+pub mod utils;
+
+pub use crate::jbod::disks::DiskShelf;
+pub use crate::jbod::enclosure::BackPlane;
+pub use crate::jbod::scsi::Scsi;
+pub use crate::jbod::ses::Ses;
+pub use crate::jbod::slotmap::SlotMap;
+pub use crate::utils::error::Error::JbodError;
+pub use crate::utils::helper::Util;
+pub use crate::utils::mdstat::MdStat;
+pub use crate::utils::output::Output;
+pub use crate::utils::runner::Runner::Context;
+pub use crate::utils::template::Template;
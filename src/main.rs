@@ -28,7 +28,6 @@
  * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
  */
 
-#[forbid(unsafe_code)]
 use clap::{App, Arg, ArgMatches, SubCommand};
 use colored::*;
 use nix::{
@@ -40,19 +39,18 @@ use std::process::{exit, Command};
 extern crate prettytable;
 use prettytable::{Cell, Row};
 
-mod jbod;
-mod utils;
-use crate::jbod::disks::DiskShelf;
-use crate::jbod::enclosure::BackPlane;
-use crate::utils::helper::Util;
+use jbod::BackPlane::BayState;
+use jbod::DiskShelf::LedState;
+use jbod::Template::{Scope, Template};
+use jbod::{BackPlane, Context, DiskShelf, JbodError, MdStat, Output, Ses, SlotMap, Util};
 
 /// Fallback help function, we should never fall here
 fn help() {
     println!("Use command with help option");
 }
 
-/// Given a string representing a temperature like: [0-9]+ it will
-/// return colored string first for the temperature second for the unit.
+/// Given a temperature in Celsius it will return colored string first
+/// for the temperature second for the unit.
 ///
 /// Coloration:
 ///
@@ -63,8 +61,9 @@ fn help() {
 /// If temperature is not readable it return `None` it's caller responsibility
 /// to report it properly.
 ///
-fn color_temp(temperature: &str) -> Option<(ColoredString, ColoredString)> {
-    let temp_conv = temperature.parse::<i32>().ok()?;
+fn color_temp(temperature: Option<i32>) -> Option<(ColoredString, ColoredString)> {
+    let temp_conv = temperature?;
+    let temperature = temp_conv.to_string();
     let coloreds = if temp_conv > 45 && temp_conv <= 50 {
         (temperature.yellow().bold(),
         "c".yellow().bold())
@@ -76,9 +75,36 @@ fn color_temp(temperature: &str) -> Option<(ColoredString, ColoredString)> {
     Some(coloreds)
 }
 
-/// TODO: Rework error handling, perhaps we don't need return Result
-///
-/// Returns an empty Result for now.
+/// The line printed for every disk by `jbod list -d`.
+const DISK_LINE: &str = "     `+- Disk: {device_path:<10} Map: {device_map:<10} Slot: {slot:<10} \
+    Vendor: {vendor:<10} Model: {model:<10} Serial: {serial:<10} Temp: {temperature:<4} Fw: {fw_revision:<10} Flags: {flags}";
+
+/// Colors a field of `DISK_LINE`, the missing values are highlighted.
+fn style_disk_field(field: &str, value: &str) -> String {
+    match field {
+        "device_map" if value.is_empty() => "NONE".yellow().to_string(),
+        "serial" if value.is_empty() => "N/A".yellow().to_string(),
+        "flags" if value.is_empty() => "-".to_string(),
+        "flags" => value.red().bold().to_string(),
+        "temperature" => match color_temp(value.parse().ok()) {
+            Some((temp_colored, unit_colored)) => format!("{}{}", temp_colored, unit_colored),
+            None => "ERR".red().bold().blink().to_string(),
+        },
+        "device_path" | "device_map" | "slot" => value.green().to_string(),
+        _ => value.blue().to_string(),
+    }
+}
+
+/// Colors the state of a slot, the slots needing a look are in red.
+fn color_bay_state(state: BayState) -> ColoredString {
+    match state {
+        BayState::PresentOk => state.to_string().green(),
+        BayState::Empty | BayState::Unsupported => state.to_string().yellow(),
+        BayState::PresentFailed | BayState::NotResponding => state.to_string().red().bold(),
+    }
+}
+
+/// Returns an error if the enclosures or disks could not be probed.
 ///
 /// This function is used in the `list` menu option,
 /// it combines the options for list the enclosures,
@@ -88,69 +114,403 @@ fn color_temp(temperature: &str) -> Option<(ColoredString, ColoredString)> {
 ///
 /// * `option` - clappy's ArgMatches
 ///
-fn enclosure_overview(option: &ArgMatches) -> Result<(), ()> {
+fn enclosure_overview(option: &ArgMatches) -> Result<(), JbodError> {
+    let mut ctx = Context::default();
+    if let Some(root) = option.value_of("sysfs-root") {
+        ctx = ctx.with_sysfs_root(root);
+    }
+    if let Some(jobs) = option.value_of("jobs") {
+        let jobs = jobs.parse::<usize>().map_err(|_| {
+            JbodError::InvalidArgument(format!("--jobs expects a number, got {}", jobs))
+        })?;
+        ctx = ctx.with_concurrency(jobs);
+    }
     let disks_option = option.is_present("disks");
     let enclosure_option = option.is_present("enclosure");
     let fan_option = option.is_present("fan");
+    let psu_option = option.is_present("psu");
+    let sensors_option = option.is_present("sensors");
+
+    if option.value_of("output") == Some("json") {
+        let mut enclosures = BackPlane::get_enclosure(&ctx)?;
+        for enclosure in enclosures.iter_mut() {
+            // A failing enclosure is reported in its `errors`, it does not
+            // hide the other enclosures from the document.
+            if disks_option {
+                match DiskShelf::get_disks_per_enclosure(&ctx, enclosure) {
+                    Ok(disks) => enclosure.disks = disks,
+                    Err(err) => enclosure.errors.push(err.to_string()),
+                }
+                if let Err(err) = DiskShelf::get_enclosure_slots(&ctx, enclosure) {
+                    enclosure.errors.push(err.to_string());
+                }
+            }
+            if fan_option || psu_option || sensors_option {
+                if let Err(err) = BackPlane::get_enclosure_elements(&ctx, enclosure) {
+                    enclosure.errors.push(err.to_string());
+                }
+            }
+        }
+        println!("{}", Output::to_json(&enclosures)?);
+        return Ok(());
+    }
+
+    if let Some(format) = option.value_of("format") {
+        let scope = if disks_option {
+            Scope::Disk
+        } else if fan_option {
+            Scope::Fan
+        } else {
+            Scope::Enclosure
+        };
+        let template = Template::parse(format, scope)?;
+        let mut enclosures = BackPlane::get_enclosure(&ctx)?;
+        for enclosure in enclosures.iter_mut() {
+            if scope == Scope::Disk || template.uses_disks() {
+                match DiskShelf::get_disks_per_enclosure(&ctx, enclosure) {
+                    Ok(disks) => enclosure.disks = disks,
+                    Err(err) => enclosure.errors.push(err.to_string()),
+                }
+            }
+            if scope == Scope::Fan || template.uses_fans() {
+                match BackPlane::get_fans_per_enclosure(&ctx, enclosure) {
+                    Ok(fans) => enclosure.fans = fans,
+                    Err(err) => enclosure.errors.push(err.to_string()),
+                }
+            }
+        }
+        print!("{}", template.render(&enclosures));
+        for err in enclosures.iter().flat_map(|enc| enc.errors.iter()) {
+            eprintln!("{} {}", "Warning:".yellow().bold(), err);
+        }
+        return Ok(());
+    }
+
+    if let Some(format @ ("csv" | "tsv")) = option.value_of("output") {
+        if !disks_option {
+            return Err(JbodError::InvalidArgument(format!(
+                "--output {} lists the disks, use it with -d",
+                format
+            )));
+        }
+        let columns: Vec<&str> = option
+            .value_of("columns")
+            .map(|c| c.split(',').map(str::trim).collect())
+            .unwrap_or_default();
+        let separator = if format == "csv" { ',' } else { '\t' };
+        let disks = DiskShelf::jbod_disk_map(&ctx)?;
+        print!("{}", Output::to_delimited(&disks, &columns, separator)?);
+        return Ok(());
+    }
 
     // If the options `-ed` or `-d` are used, it shows
     // the enclosure and disks altogether.
-    if enclosure_option && disks_option || disks_option {
-        let enclosure = BackPlane::get_enclosure();
-        let mut disks = DiskShelf::jbod_disk_map();
-        disks.sort_by_key(|d| d.slot.clone());
+    if disks_option {
+        let disk_line = Template::parse(DISK_LINE, Scope::Disk)?;
+        let enclosure = DiskShelf::jbod_inventory(&ctx)?;
         for enc in enclosure {
             print!("{}", enc);
             println!("     '");
-            for disk in &disks {
-                if enc.slot == disk.enclosure {
-                    print!("     `+-");
-                    print!(" Disk: {:<10}", disk.device_path.green(),);
-                    if disk.device_map == "NONE" {
-                        print!(" Map: {:<10}", disk.device_map.yellow());
-                    } else {
-                        print!(" Map: {:<10}", disk.device_map.green());
-                    }
-                    print!(" Slot: {:<10}", disk.slot.green());
-                    print!(" Vendor: {:<10}", disk.vendor.blue());
-                    print!(" Model: {:<10}", disk.model.blue());
-                    print!(" Serial: {:<10} ", disk.serial.blue());
-                    match color_temp(&disk.temperature) {
-                        Some((temp_colored, unit_colored)) => print!("Temp: {}{:<2}", temp_colored, unit_colored),
-                        None => print!("Temp: {:<4}", "ERR".red().bold().blink()),
-                    }
-                    println!(" Fw: {}", disk.fw_revision.blue());
-                }
+            print!("{}", disk_line.render_with(std::slice::from_ref(&enc), style_disk_field));
+            for slot in enc.slots.iter().filter(|s| s.state != BayState::PresentOk) {
+                println!(
+                    "     `+- Slot: {:<10} {}",
+                    slot.number.to_string().green(),
+                    color_bay_state(slot.state)
+                );
+            }
+            for err in enc.errors.iter() {
+                eprintln!("{} {}", "Warning:".yellow().bold(), err);
             }
         }
     // Here it shows only the enclosures.
     } else if enclosure_option && !disks_option {
-        let enclosure = BackPlane::get_enclosure();
+        let enclosure = BackPlane::get_enclosure(&ctx)?;
         for enc in enclosure {
             print!("{}", enc);
         }
     // Here it shows the FAN.
     } else if fan_option {
-        let enclosure_fan = BackPlane::get_enclosure_fan();
-        let mut fan_table = BackPlane::create_fan_table();
+        let enclosure_fan = BackPlane::get_enclosure_fan(&ctx)?;
+        let mut rows = Vec::new();
         for fan in enclosure_fan {
-            fan_table.add_row(Row::new(vec![
+            let flags = fan.flags.flags();
+            rows.push(Row::new(vec![
                 Cell::new(&fan.slot),
                 Cell::new(&fan.index),
                 Cell::new(&fan.description),
-                Cell::new(&fan.comment),
+                Cell::new(&fan.status.to_string()),
+                Cell::new(&format!(
+                    "{} ({})",
+                    Ses::fan_speed_code_desc(fan.speed_code),
+                    fan.speed_code
+                )),
                 Cell::new(&fan.speed.to_string()),
+                Cell::new(&if flags.is_empty() { "-".to_string() } else { flags.join(", ") }),
             ]));
         }
-        fan_table.printstd();
+        BackPlane::create_table(
+            &["SLOT", "IDENT", "DESCRIPTION", "STATUS", "SPEED", "RPM", "FLAGS"],
+            rows,
+        )
+        .printstd();
+    // Here it shows the power supplies.
+    } else if psu_option {
+        let mut rows = Vec::new();
+        for enclosure in BackPlane::get_enclosure(&ctx)? {
+            for psu in BackPlane::get_psus_per_enclosure(&ctx, &enclosure)? {
+                let flags = psu.flags.flags();
+                rows.push(Row::new(vec![
+                    Cell::new(&psu.slot),
+                    Cell::new(&psu.index),
+                    Cell::new(&psu.description),
+                    Cell::new(&psu.status.to_string()),
+                    Cell::new(&if flags.is_empty() { "-".to_string() } else { flags.join(", ") }),
+                ]));
+            }
+        }
+        BackPlane::create_table(
+            &["SLOT", "IDENT", "DESCRIPTION", "STATUS", "FLAGS"],
+            rows,
+        )
+        .printstd();
+    // Here it shows the sensors.
+    } else if sensors_option {
+        let mut rows = Vec::new();
+        for enclosure in BackPlane::get_enclosure(&ctx)? {
+            for sensor in BackPlane::get_sensors_per_enclosure(&ctx, &enclosure)? {
+                let flags = sensor.flags.flags();
+                let value = match sensor.value {
+                    Some(value) => format!("{} {}", value, sensor.kind.unit()),
+                    None => "-".to_string(),
+                };
+                rows.push(Row::new(vec![
+                    Cell::new(&sensor.slot),
+                    Cell::new(&sensor.index),
+                    Cell::new(&sensor.description),
+                    Cell::new(&sensor.status.to_string()),
+                    Cell::new(&value),
+                    Cell::new(&if flags.is_empty() { "-".to_string() } else { flags.join(", ") }),
+                ]));
+            }
+        }
+        BackPlane::create_table(
+            &["SLOT", "IDENT", "DESCRIPTION", "STATUS", "VALUE", "FLAGS"],
+            rows,
+        )
+        .printstd();
     }
 
     Ok(())
 }
 
-/// TODO: Rework error handling, perhaps we don't need return Result 
+/// Returns an error if the enclosures or disks could not be probed.
 ///
-/// Returns an empty Result for now.
+/// This function is used in the `map` menu option, it draws the slots of
+/// every enclosure as they are laid out in the rack, with the slot states
+/// reported by the enclosure.
+///
+/// # Arguments
+///
+/// * `option` - clappy's ArgMatches
+///
+fn enclosure_map(option: &ArgMatches) -> Result<(), JbodError> {
+    let mut ctx = Context::default();
+    if let Some(root) = option.value_of("sysfs-root") {
+        ctx = ctx.with_sysfs_root(root);
+    }
+    let unicode = !option.is_present("ascii");
+
+    for enclosure in DiskShelf::jbod_inventory(&ctx)? {
+        println!("{}", SlotMap::render(&enclosure, unicode));
+        for err in enclosure.errors.iter() {
+            eprintln!("{} {}", "Warning:".yellow().bold(), err);
+        }
+    }
+
+    Ok(())
+}
+
+/// This function is used in the `fan set` menu option, it sets the speed
+/// code of the FAN of one enclosure.
+///
+/// # Arguments
+///
+/// * `option` - clappy's ArgMatches
+///
+fn fan_control(option: &ArgMatches) -> Result<(), JbodError> {
+    let ctx = Context::default();
+    let serial = option.value_of("enclosure").unwrap_or_default();
+    let index = option.value_of("index");
+    let speed_code = option
+        .value_of("speed-code")
+        .unwrap_or_default()
+        .parse::<u8>()
+        .map_err(|_| {
+            JbodError::InvalidArgument("--speed-code expects a number from 1 to 7".to_string())
+        })?;
+    let dry_run = option.is_present("dry-run");
+
+    let page = BackPlane::set_fan_speed(&ctx, serial, index, speed_code, dry_run)?;
+    if dry_run {
+        println!("Enclosure control page for {}, not sent:", serial);
+        println!("{}", Ses::hex_dump(&page));
+    } else {
+        println!(
+            "FAN {} of enclosure {} {} ({})",
+            index.unwrap_or("all"),
+            serial,
+            Ses::fan_speed_code_desc(speed_code),
+            speed_code
+        );
+    }
+
+    Ok(())
+}
+
+/// This function is used in the `alarm` menu option, it lists the audible
+/// alarms of every enclosure or mutes/unmutes the alarms of one enclosure.
+///
+/// # Arguments
+///
+/// * `option` - clappy's ArgMatches
+///
+fn alarm_control(option: &ArgMatches) -> Result<(), JbodError> {
+    let ctx = Context::default();
+    match option.subcommand() {
+        Some(("status", _)) => {
+            let mut rows = Vec::new();
+            for alarm in BackPlane::get_enclosure_alarm(&ctx)? {
+                let flags = alarm.flags.flags();
+                rows.push(Row::new(vec![
+                    Cell::new(&alarm.slot),
+                    Cell::new(alarm.serial.as_deref().unwrap_or("NONE")),
+                    Cell::new(&alarm.index),
+                    Cell::new(&alarm.description),
+                    Cell::new(&alarm.status.to_string()),
+                    Cell::new(&if flags.is_empty() { "-".to_string() } else { flags.join(", ") }),
+                ]));
+            }
+            BackPlane::create_table(
+                &["SLOT", "SERIAL", "IDENT", "DESCRIPTION", "STATUS", "FLAGS"],
+                rows,
+            )
+            .printstd();
+        }
+        Some((action, m)) => {
+            let serial = m.value_of("enclosure").unwrap_or_default();
+            let mute = action == "mute";
+            let dry_run = m.is_present("dry-run");
+
+            let page = BackPlane::set_alarm_mute(&ctx, serial, mute, dry_run)?;
+            if dry_run {
+                println!("Enclosure control page for {}, not sent:", serial);
+                println!("{}", Ses::hex_dump(&page));
+            } else {
+                println!("Audible alarm of enclosure {} {}d", serial, action);
+            }
+        }
+        None => (),
+    }
+
+    Ok(())
+}
+
+/// Returns an error if the led could not be switched
+///
+/// This function is used in the `led` menu option, it handles the disk led
+/// manipulation.
+///
+/// # Arguments
+///
+/// * `option` - clappy's ArgMatches
+///
+fn led_switch(option: &ArgMatches) -> Result<(), JbodError> {
+    let ctx = Context::default();
+    let state = match (option.is_present("on"), option.is_present("off")) {
+        (true, true) => {
+            return Err(JbodError::InvalidArgument(format!(
+                "Not christmas yet {}{}{}!",
+                ":".green().bold(),
+                "_".yellow().bold().blink(),
+                ")".red().bold()
+            )))
+        }
+        (true, false) => LedState::On,
+        (false, true) => LedState::Off,
+        (false, false) => return Ok(()),
+    };
+
+    if let Some(disk) = option.value_of("locate") {
+        let disk = DiskShelf::set_disk_led_locate(&ctx, disk, state)?;
+        let slot = match state {
+            LedState::On => disk.slot.to_string().yellow().blink().bold(),
+            LedState::Off => disk.slot.to_string().green().bold(),
+        };
+        println!("Disk slot: {} {}", slot, state.sysfs_value());
+    }
+    if let Some(disk) = option.value_of("fault") {
+        let disk = DiskShelf::set_disk_led_fault(&ctx, disk, state)?;
+        let slot = match state {
+            LedState::On => disk.slot.to_string().red().blink().bold(),
+            LedState::Off => disk.slot.to_string().green().bold(),
+        };
+        println!("Disk slot: {} {}", slot, state.sysfs_value());
+    }
+
+    Ok(())
+}
+
+/// This function is used in the `led sync-raid` menu option, it sets the
+/// array bits of the slots from the state of the md arrays.
+///
+/// # Arguments
+///
+/// * `option` - clappy's ArgMatches
+///
+fn raid_sync(option: &ArgMatches) -> Result<(), JbodError> {
+    let ctx = Context::default();
+    let dry_run = option.is_present("dry-run");
+    let arrays = MdStat::read(&ctx)?;
+
+    for mut enclosure in BackPlane::get_enclosure(&ctx)? {
+        enclosure.disks = DiskShelf::get_disks_per_enclosure(&ctx, &enclosure)?;
+        let flags = DiskShelf::get_raid_slot_flags(&ctx, &enclosure.disks, &arrays);
+        let (changes, page) =
+            BackPlane::sync_slot_array_flags(&ctx, &enclosure, &flags.requested, dry_run)?;
+        let name = enclosure.serial.as_deref().unwrap_or(&enclosure.slot);
+
+        for (slot, device_map) in flags.skipped.iter() {
+            eprintln!(
+                "{} enclosure {} slot {} {}: held by a device that is not an md array, skipped",
+                "Warning:".yellow().bold(),
+                name,
+                slot,
+                device_map
+            );
+        }
+
+        for change in changes.iter() {
+            let (on, off) = change.diff();
+            println!(
+                "Enclosure {} slot {} {}: {}{}",
+                name,
+                change.slot.number.to_string().green(),
+                change.device_map.as_deref().unwrap_or("NONE"),
+                on.iter().map(|f| format!("+{} ", f)).collect::<String>().yellow(),
+                off.iter().map(|f| format!("-{} ", f)).collect::<String>().green(),
+            );
+        }
+        if let (true, Some(page)) = (dry_run, page) {
+            println!("Enclosure control page for {}, not sent:", name);
+            println!("{}", Ses::hex_dump(&page));
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns an error if the fork failed.
 ///
 /// This function forks another binary for the prometheus-exporter. 
 ///
@@ -158,9 +518,10 @@ fn enclosure_overview(option: &ArgMatches) -> Result<(), ()> {
 ///
 /// * `option` - clappy's ArgMatches
 ///
-fn fork_prometheus(option: &ArgMatches) -> Result<(), ()> {
+fn fork_prometheus(option: &ArgMatches) -> Result<(), JbodError> {
     let mut default_port = "9945";
     let mut default_address = "0.0.0.0";
+    let mut default_interval = "30";
 
     if let Some(port) = option.value_of("port") {
         default_port = port;
@@ -170,29 +531,77 @@ fn fork_prometheus(option: &ArgMatches) -> Result<(), ()> {
         default_address = ip;
     }
 
+    if let Some(interval) = option.value_of("interval") {
+        default_interval = interval;
+    }
+
     match unsafe { fork() } {
         Ok(ForkResult::Parent { child }) => {
             println!("prometheus-exporter pid: {:?}", child);
-            waitpid(Some(child), None).unwrap();
+            waitpid(Some(child), None).ok();
             exit(0);
         }
 
         Ok(ForkResult::Child) => {
             Command::new(Util::JBOD_EXPORTER)
-                .args(&[default_address, default_port])
+                .args([default_address, default_port, default_interval])
                 .spawn()
                 .expect("Failed to spawn the target process");
             exit(0);
         }
-        Err(_) => println!("Fork Failed"),
+        Err(err) => Err(JbodError::Io("fork".to_string(), err.into())),
     }
+}
 
-    Ok(())
+/// Returns the exit code for an error, following sysexits(3).
+fn exit_code(err: &JbodError) -> i32 {
+    match err {
+        JbodError::InvalidArgument(_) => 64,
+        JbodError::Parse(_) => 65,
+        JbodError::MissingTool(_) | JbodError::SysfsMissing(_) => 69,
+        JbodError::DeviceVanished(_) | JbodError::ControlRejected(_) | JbodError::Io(_, _) => 74,
+        JbodError::PermissionDenied(_) => 77,
+    }
+}
+
+/// Prints the error in a human friendly way and exits with its exit code.
+fn report(err: JbodError) -> ! {
+    match &err {
+        JbodError::MissingTool(tool) => {
+            println!(
+                "{} {} {}",
+                "==> ".blue().bold(),
+                "Packages missing".bold(),
+                " <==".blue().bold()
+            );
+            print!("{}", ":: ".bold().red());
+            print!("Install package ");
+            println!("{}", tool.rsplit('/').next().unwrap_or(tool).red().bold().blink());
+        }
+        JbodError::SysfsMissing(_) => {
+            println!(
+                "{} {} {}",
+                "==> ".red().bold(),
+                "jbod-rs not supported in this machine".bold(),
+                " <==".red().bold()
+            );
+            print!("{}", ":: ".bold().red());
+            println!("Use one of these alternatives: ");
+            println!(" - lsscsi");
+            println!(" - sas-lsi-tools");
+            println!(" - sas2ircu-status");
+            println!(" - sg3-utils");
+        }
+        _ => eprintln!("{} {}", "Error:".red().bold(), err),
+    }
+    exit(exit_code(&err));
 }
 
 /// The main function that creates the menu.
 fn main() {
-    Util::verify_binary_needed();
+    if let Err(err) = Util::verify_binary_needed() {
+        report(err);
+    }
 
     let matches = App::new("jbod")
         .version("0.0.1")
@@ -229,6 +638,83 @@ fn main() {
                         .takes_value(false)
                         .exclusive(false)
                         .help("List fan"),
+                )
+                .arg(
+                    Arg::with_name("psu")
+                        .long("psu")
+                        .required(false)
+                        .takes_value(false)
+                        .help("List power supplies"),
+                )
+                .arg(
+                    Arg::with_name("sensors")
+                        .long("sensors")
+                        .required(false)
+                        .takes_value(false)
+                        .help("List enclosure sensors"),
+                )
+                .arg(
+                    Arg::with_name("sysfs-root")
+                        .long("sysfs-root")
+                        .required(false)
+                        .value_name("DIR")
+                        .takes_value(true)
+                        .help("Read sysfs from DIR instead of /sys, without sending SCSI commands"),
+                )
+                .arg(
+                    Arg::with_name("jobs")
+                        .short('j')
+                        .long("jobs")
+                        .required(false)
+                        .value_name("N")
+                        .takes_value(true)
+                        .help("Probe up to N disks at the same time"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short('o')
+                        .long("output")
+                        .required(false)
+                        .value_name("FORMAT")
+                        .takes_value(true)
+                        .possible_values(["text", "json", "csv", "tsv"])
+                        .default_value("text")
+                        .help("Output format, json prints the enclosures with their disks (-d), fan, power supplies and sensors (-f, --psu, --sensors), csv and tsv print the disks (-d)"),
+                )
+                .arg(
+                    Arg::with_name("columns")
+                        .long("columns")
+                        .required(false)
+                        .value_name("COLUMNS")
+                        .takes_value(true)
+                        .help("Comma separated disk columns for csv and tsv, example: slot,serial,model,fw_revision"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .required(false)
+                        .value_name("TEMPLATE")
+                        .takes_value(true)
+                        .help("Print every enclosure (-e), disk (-d) or fan (-f) with TEMPLATE, example: '{enclosure}:{slot} {device_map} {serial}'"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("map")
+                .about("Draw the slots of every enclosure")
+                .arg(
+                    Arg::with_name("ascii")
+                        .long("ascii")
+                        .required(false)
+                        .takes_value(false)
+                        .help("Draw the grid with ASCII characters"),
+                )
+                .arg(
+                    Arg::with_name("sysfs-root")
+                        .long("sysfs-root")
+                        .required(false)
+                        .value_name("DIR")
+                        .takes_value(true)
+                        .help("Read sysfs from DIR instead of /sys, without sending SCSI commands"),
                 ),
         )
         .subcommand(
@@ -253,7 +739,105 @@ fn main() {
                         .takes_value(true),
                 )
                 .arg(Arg::with_name("on").long("on").required(false))
-                .arg(Arg::with_name("off").long("off").required(false)),
+                .arg(Arg::with_name("off").long("off").required(false))
+                .subcommand(
+                    SubCommand::with_name("sync-raid")
+                        .about("Set the rebuild, hot spare and critical/failed array slot bits from /proc/mdstat")
+                        .arg(
+                            Arg::with_name("dry-run")
+                                .long("dry-run")
+                                .required(false)
+                                .takes_value(false)
+                                .help("Print the changes and the control page instead of sending it"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("fan")
+                .about("Control the enclosure FAN")
+                .arg_required_else_help(true)
+                .subcommand(
+                    SubCommand::with_name("set")
+                        .about("Set the speed code of the FAN of one enclosure")
+                        .arg(
+                            Arg::with_name("enclosure")
+                                .long("enclosure")
+                                .required(true)
+                                .value_name("SERIAL")
+                                .takes_value(true)
+                                .help("Serial number of the enclosure"),
+                        )
+                        .arg(
+                            Arg::with_name("speed-code")
+                                .long("speed-code")
+                                .required(true)
+                                .value_name("1-7")
+                                .takes_value(true)
+                                .help("Requested speed code, from 1 (lowest) to 7 (highest)"),
+                        )
+                        .arg(
+                            Arg::with_name("index")
+                                .long("index")
+                                .required(false)
+                                .value_name("INDEX")
+                                .takes_value(true)
+                                .help("Only set the FAN with this index, example: 3,0"),
+                        )
+                        .arg(
+                            Arg::with_name("dry-run")
+                                .long("dry-run")
+                                .required(false)
+                                .takes_value(false)
+                                .help("Print the control page instead of sending it"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("alarm")
+                .about("Audible alarm of the enclosures")
+                .arg_required_else_help(true)
+                .subcommand(
+                    SubCommand::with_name("status")
+                        .about("List the audible alarms of every enclosure"),
+                )
+                .subcommand(
+                    SubCommand::with_name("mute")
+                        .about("Mute the audible alarms of one enclosure")
+                        .arg(
+                            Arg::with_name("enclosure")
+                                .long("enclosure")
+                                .required(true)
+                                .value_name("SERIAL")
+                                .takes_value(true)
+                                .help("Serial number of the enclosure"),
+                        )
+                        .arg(
+                            Arg::with_name("dry-run")
+                                .long("dry-run")
+                                .required(false)
+                                .takes_value(false)
+                                .help("Print the control page instead of sending it"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("unmute")
+                        .about("Unmute the audible alarms of one enclosure")
+                        .arg(
+                            Arg::with_name("enclosure")
+                                .long("enclosure")
+                                .required(true)
+                                .value_name("SERIAL")
+                                .takes_value(true)
+                                .help("Serial number of the enclosure"),
+                        )
+                        .arg(
+                            Arg::with_name("dry-run")
+                                .long("dry-run")
+                                .required(false)
+                                .takes_value(false)
+                                .help("Print the control page instead of sending it"),
+                        ),
+                ),
         )
         .subcommand(
             SubCommand::with_name("prometheus")
@@ -273,15 +857,39 @@ fn main() {
                         .required(false)
                         .value_name("IPADDRESS")
                         .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("interval")
+                        .long("interval")
+                        .required(false)
+                        .value_name("SECONDS")
+                        .takes_value(true)
+                        .help("Seconds between two collections, default 30"),
                 ),
         )
         .get_matches();
 
     // Here it matches the menu options with its respective functions.
-    match matches.subcommand() {
+    let result = match matches.subcommand() {
         Some(("list", m)) => enclosure_overview(m),
-        Some(("led", m)) => DiskShelf::jbod_led_switch(m),
+        Some(("map", m)) => enclosure_map(m),
+        Some(("led", m)) => match m.subcommand() {
+            Some(("sync-raid", m)) => raid_sync(m),
+            _ => led_switch(m),
+        },
+        Some(("fan", m)) => match m.subcommand() {
+            Some(("set", m)) => fan_control(m),
+            _ => Ok(()),
+        },
+        Some(("alarm", m)) => alarm_control(m),
         Some(("prometheus", m)) => fork_prometheus(m),
-        _ => Ok(help()),
+        _ => {
+            help();
+            Ok(())
+        }
     };
+
+    if let Err(err) = result {
+        report(err);
+    }
 }
@@ -35,16 +35,15 @@ use std::net::SocketAddr;
 
 use std::env;
 use std::result::Result;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use warp::{Filter, Rejection, Reply};
 use prometheus::{
-    IntGauge, IntGaugeVec, Opts, Registry,
+    Gauge, GaugeVec, IntGauge, IntGaugeVec, Opts, Registry,
 };
 
-mod jbod;
-mod utils;
-use crate::jbod::disks::DiskShelf;
-use crate::jbod::enclosure::BackPlane;
-use crate::utils::helper::Util;
+use jbod::BackPlane::SensorKind;
+use jbod::{BackPlane, Context, DiskShelf, Util};
 
 // Declare code to be executed at runtime, this includes anything requiring
 // heap allocations and function calls to be computed.
@@ -65,9 +64,73 @@ lazy_static! {
 
     pub static ref JBOD_FAN_RPM: IntGaugeVec =
         IntGaugeVec::new(
-        Opts::new("jbod_fan_rpm", "The RPM speed of FAN components, device and slot"),
-        &["device", "slot"]
+        Opts::new("jbod_fan_rpm", "The RPM speed of FAN components, enclosure, device and slot"),
+        &["enclosure", "device", "slot"]
     ).expect("metric can be created");
+
+    pub static ref JBOD_SLOT_FLAG: IntGaugeVec =
+        IntGaugeVec::new(
+        Opts::new("jbod_slot_flag", "State bits of the enclosure slots: swap, predicted_failure, hot_spare, rebuild_remap, ..."),
+        &["enclosure", "slot", "flag"]
+    ).expect("metric can be created");
+
+    pub static ref JBOD_FAN_STATUS: IntGaugeVec =
+        IntGaugeVec::new(
+        Opts::new("jbod_fan_status",
+            "SES status code of FAN: 0 unsupported, 1 OK, 2 critical, 3 noncritical, 4 unrecoverable, 5 not installed, 6 unknown, 7 not available, 8 no access allowed"),
+        &["enclosure", "device", "slot"]
+    ).expect("metric can be created");
+
+    pub static ref JBOD_PSU_STATUS: IntGaugeVec =
+        IntGaugeVec::new(
+        Opts::new("jbod_psu_status",
+            "SES status code of power supplies: 0 unsupported, 1 OK, 2 critical, 3 noncritical, 4 unrecoverable, 5 not installed, 6 unknown, 7 not available, 8 no access allowed"),
+        &["enclosure", "description", "index"]
+    ).expect("metric can be created");
+
+    pub static ref JBOD_ENCLOSURE_TEMPERATURE: GaugeVec =
+        GaugeVec::new(
+        Opts::new("jbod_enclosure_temperature_celsius", "Temperature of the enclosure sensors"),
+        &["enclosure", "sensor", "index"]
+    ).expect("metric can be created");
+
+    pub static ref JBOD_ENCLOSURE_VOLTAGE: GaugeVec =
+        GaugeVec::new(
+        Opts::new("jbod_enclosure_voltage_volts", "Voltage of the enclosure sensors"),
+        &["enclosure", "sensor", "index"]
+    ).expect("metric can be created");
+
+    pub static ref JBOD_ENCLOSURE_CURRENT: GaugeVec =
+        GaugeVec::new(
+        Opts::new("jbod_enclosure_current_amperes", "Current of the enclosure sensors"),
+        &["enclosure", "sensor", "index"]
+    ).expect("metric can be created");
+
+    pub static ref JBOD_COLLECTION_ERRORS: IntGaugeVec =
+        IntGaugeVec::new(
+        Opts::new("jbod_collection_errors",
+            "Number of failures while collecting an enclosure, its disks or its pages, during the last collection"),
+        &["enclosure"]
+    ).expect("metric can be created");
+
+    pub static ref JBOD_LAST_COLLECTION_TIMESTAMP: Gauge =
+        Gauge::new("jbod_last_collection_timestamp_seconds",
+            "Unix time of the last successful collection").expect("metric can be created");
+
+    // The latest inventory collected by `collector()`, every scrape renders
+    // the metrics from it instead of probing the enclosures again.
+    pub static ref SNAPSHOT: Mutex<Snapshot> = Mutex::new(Snapshot::default());
+}
+
+/// The default number of seconds between two collections.
+const DEFAULT_INTERVAL: u64 = 30;
+
+/// The inventory of the last successful collection.
+#[derive(Default)]
+pub struct Snapshot {
+    enclosures: Vec<BackPlane::Enclosure>,
+    /// Unix time of the collection, `None` until the first one succeeds.
+    timestamp: Option<f64>,
 }
 
 /// Here we register the metrics, this function is called in the `main()`.
@@ -78,6 +141,22 @@ fn register_metrics() {
         .expect("collector can be registered");
     REGISTRY.register(Box::new(JBOD_FAN_RPM.clone()))
         .expect("collector can be registered");
+    REGISTRY.register(Box::new(JBOD_SLOT_FLAG.clone()))
+        .expect("collector can be registered");
+    REGISTRY.register(Box::new(JBOD_FAN_STATUS.clone()))
+        .expect("collector can be registered");
+    REGISTRY.register(Box::new(JBOD_PSU_STATUS.clone()))
+        .expect("collector can be registered");
+    REGISTRY.register(Box::new(JBOD_ENCLOSURE_TEMPERATURE.clone()))
+        .expect("collector can be registered");
+    REGISTRY.register(Box::new(JBOD_ENCLOSURE_VOLTAGE.clone()))
+        .expect("collector can be registered");
+    REGISTRY.register(Box::new(JBOD_ENCLOSURE_CURRENT.clone()))
+        .expect("collector can be registered");
+    REGISTRY.register(Box::new(JBOD_COLLECTION_ERRORS.clone()))
+        .expect("collector can be registered");
+    REGISTRY.register(Box::new(JBOD_LAST_COLLECTION_TIMESTAMP.clone()))
+        .expect("collector can be registered");
 }
 
 // Index handler.
@@ -85,49 +164,157 @@ async fn index_handler() -> Result<impl Reply, Rejection> {
     Ok("")
 }
 
-/// Returns an `i64` with the total number of enclosures.
-async fn number_of_enclosure_metrics() -> i64 {
-    let enclosure = BackPlane::get_enclosure();
-    return(enclosure.len() as i64)
-}
-
-/// Returns Result with Reply and Rejection.
+/// Updates the metrics from the enclosures, their disks and FAN.
 ///
-/// This function updates the prometheus-exporter metrics.
-/// Also here we can find the logic behind each metric.
-async fn metrics_handler() -> Result<impl Reply, Rejection> {
-    use prometheus::Encoder;
-    let encoder = prometheus::TextEncoder::new();
+/// The vectors are reset first so a disk or FAN that went away does not
+/// keep its last value forever.
+fn update_metrics(enclosures: &[BackPlane::Enclosure]) {
+    JBOD_FAN_RPM.reset();
+    JBOD_FAN_STATUS.reset();
+    JBOD_SLOT_FLAG.reset();
+    JBOD_SLOT_TEMPERATURE.reset();
+    JBOD_PSU_STATUS.reset();
+    JBOD_ENCLOSURE_TEMPERATURE.reset();
+    JBOD_ENCLOSURE_VOLTAGE.reset();
+    JBOD_ENCLOSURE_CURRENT.reset();
+    JBOD_COLLECTION_ERRORS.reset();
 
     // Enclosure FAN rpm
-    let mut enclosure_fan = BackPlane::get_enclosure_fan();
-    enclosure_fan.sort_by_key(|f| f.index.clone());
-    for fan in enclosure_fan.iter() {
-        JBOD_FAN_RPM.with_label_values(&[&fan.description, &fan.index])
-            .set(fan.speed);
+    for enclosure in enclosures.iter() {
+        let name = enclosure.serial.as_deref().unwrap_or(&enclosure.slot);
+        for fan in enclosure.fans.iter() {
+            JBOD_FAN_RPM.with_label_values(&[name, &fan.description, &fan.index])
+                .set(fan.speed);
+            JBOD_FAN_STATUS.with_label_values(&[name, &fan.description, &fan.index])
+                .set(fan.status.code() as i64);
+        }
+    }
+
+    // Enclosure power supplies
+    for enclosure in enclosures.iter() {
+        let name = enclosure.serial.as_deref().unwrap_or(&enclosure.slot);
+        for psu in enclosure.psus.iter() {
+            JBOD_PSU_STATUS.with_label_values(&[name, &psu.description, &psu.index])
+                .set(psu.status.code() as i64);
+        }
+    }
+
+    // Enclosure sensors, named after their descriptor or index, the index
+    // tells apart the sensors sharing a descriptor
+    for enclosure in enclosures.iter() {
+        let name = enclosure.serial.as_deref().unwrap_or(&enclosure.slot);
+        for sensor in enclosure.sensors.iter() {
+            let sensor_name = if sensor.description.is_empty() { &sensor.index } else { &sensor.description };
+            let gauge = match sensor.kind {
+                SensorKind::Temperature => &*JBOD_ENCLOSURE_TEMPERATURE,
+                SensorKind::Voltage => &*JBOD_ENCLOSURE_VOLTAGE,
+                SensorKind::Current => &*JBOD_ENCLOSURE_CURRENT,
+            };
+            if let Some(value) = sensor.value {
+                gauge.with_label_values(&[name, sensor_name, &sensor.index]).set(value);
+            }
+        }
+    }
+
+    // Enclosure slots, every flag is exported so an alert can watch it go back to 0
+    for enclosure in enclosures.iter() {
+        let name = enclosure.serial.as_deref().unwrap_or(&enclosure.slot);
+        for slot in enclosure.slots.iter() {
+            let number = slot.number.to_string();
+            for (flag, set) in slot.flags.states() {
+                JBOD_SLOT_FLAG.with_label_values(&[name, &number, flag])
+                    .set(set as i64);
+            }
+        }
     }
-    drop(enclosure_fan);
 
     // Enclosures
-    let enclosures = number_of_enclosure_metrics();
-    NUMBER_OF_ENCLOSURES.set(enclosures.await);
+    NUMBER_OF_ENCLOSURES.set(enclosures.len() as i64);
+    for enclosure in enclosures.iter() {
+        let name = enclosure.serial.as_deref().unwrap_or(&enclosure.slot);
+        JBOD_COLLECTION_ERRORS.with_label_values(&[name])
+            .set(enclosure.errors.len() as i64);
+    }
 
     // Disks slot temperature
-    let mut disks_temperature = DiskShelf::jbod_disk_map();
-    disks_temperature.sort_by_key(|d| d.slot.clone());
-    for disk in disks_temperature.iter() {
-        match disk.temperature.parse() {
-            Ok(temperature) => {
+    for disk in enclosures.iter().flat_map(|e| e.disks.iter()) {
+        match disk.temperature {
+            Some(temperature) => {
                 JBOD_SLOT_TEMPERATURE
-                .with_label_values(&[&disk.slot, &disk.enclosure])
-                .set(temperature)},
-            Err(e) => eprintln!("Failed to read temperature: {:?} of disk: {:?}", e, disk),
+                .with_label_values(&[&disk.slot.to_string(), &disk.enclosure])
+                .set(temperature as i64)},
+            None => eprintln!("Failed to read temperature of disk: {:?}", disk),
+        }
+    }
+}
+
+/// Refreshes the snapshot every `interval`, forever.
+///
+/// The probe sends SCSI commands and reads sysfs, it runs on the blocking
+/// pool so the webserver keeps answering while a collection is running.
+async fn collector(interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+
+        let inventory = tokio::task::spawn_blocking(|| {
+            DiskShelf::jbod_inventory(&Context::default())
+        }).await;
+
+        // A failing enclosure is reported in its `errors` and the others are
+        // still collected. If the enclosures cannot be listed at all we keep
+        // the previous snapshot and try again on the next interval.
+        match inventory {
+            Ok(Ok(enclosures)) => {
+                for enclosure in enclosures.iter() {
+                    let name = enclosure.serial.as_deref().unwrap_or(&enclosure.slot);
+                    for err in enclosure.errors.iter() {
+                        eprintln!("Failed to collect enclosure {}: {}", name, err);
+                    }
+                }
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|t| t.as_secs_f64())
+                    .unwrap_or_default();
+                if let Ok(mut snapshot) = SNAPSHOT.lock() {
+                    snapshot.enclosures = enclosures;
+                    snapshot.timestamp = Some(timestamp);
+                }
+            }
+            Ok(Err(e)) => eprintln!("Failed to collect the enclosures: {}", e),
+            Err(e) => eprintln!("Collection task failed: {}", e),
         }
     }
-    drop(disks_temperature);
+}
+
+/// Returns Result with Reply and Rejection.
+///
+/// This function renders the prometheus-exporter metrics from the latest
+/// snapshot, it never probes the enclosures itself.
+/// Also here we can find the logic behind each metric.
+async fn metrics_handler() -> Result<impl Reply, Rejection> {
+    use prometheus::Encoder;
+    let encoder = prometheus::TextEncoder::new();
+
+    // The lock is held until the metrics are gathered so two concurrent
+    // scrapes do not reset the vectors under each other.
+    let snapshot = SNAPSHOT.lock();
+    let families = match snapshot {
+        Ok(snapshot) => {
+            update_metrics(&snapshot.enclosures);
+            if let Some(timestamp) = snapshot.timestamp {
+                JBOD_LAST_COLLECTION_TIMESTAMP.set(timestamp);
+            }
+            REGISTRY.gather()
+        }
+        Err(e) => {
+            eprintln!("snapshot is poisoned: {}", e);
+            REGISTRY.gather()
+        }
+    };
 
     let mut buffer = Vec::new();
-    if let Err(e) = encoder.encode(&REGISTRY.gather(), &mut buffer) {
+    if let Err(e) = encoder.encode(&families, &mut buffer) {
         eprintln!("could not encode custom metrics: {}", e);
     };
 
@@ -163,6 +350,7 @@ async fn main() {
     let args: Vec<String> = env::args().collect();
     let mut port: String = "9945".to_string();
     let mut ipv4: String = "0.0.0.0".to_string();
+    let mut interval: u64 = DEFAULT_INTERVAL;
 
     if args.len() > 2 {
         if Util::is_string_numeric(&args[2]) {
@@ -174,7 +362,7 @@ async fn main() {
         let t = args[1]
             .split(".")
             .map(Util::is_string_numeric)
-            .any(|i| i == false);
+            .any(|i| !i);
         if !t {
             ipv4 = args[1].to_string();
         } else {
@@ -182,15 +370,23 @@ async fn main() {
         }
     }
 
+    if args.len() > 3 {
+        match args[3].parse::<u64>() {
+            Ok(seconds) if seconds > 0 => interval = seconds,
+            _ => println!("Interval is not a number of seconds, using default {}", interval),
+        }
+    }
+
     let adr: String = ipv4 + ":" + &port;
     let adr_convert: SocketAddr = adr.parse().expect("Could not parse SocketAddr");
 
     register_metrics();
+    tokio::spawn(collector(Duration::from_secs(interval)));
 
     let metrics_route = warp::path!("metrics").and_then(metrics_handler);
     let route = warp::path::end().and_then(index_handler);
 
-    println!("==> Started on {}", adr);
+    println!("==> Started on {}, collecting every {}s", adr, interval);
     warp::serve(metrics_route.or(route))
         .run(adr_convert)
         .await;
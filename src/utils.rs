@@ -1 +1,7 @@
+pub mod error;
 pub mod helper;
+pub mod mdstat;
+pub mod output;
+pub mod runner;
+pub mod sgio;
+pub mod template;
@@ -0,0 +1,111 @@
+/*-
+ * SPDX-License-Identifier: BSD-2-Clause
+ *
+ * BSD 2-Clause License
+ *
+ * Copyright (c) 2021-2023, Gandi S.A.S.
+ * All rights reserved.
+ *
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice, this
+ *    list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+ * AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+ * IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+ * FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+ * DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+ * SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+ * CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+ * OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+#[allow(non_snake_case)]
+pub mod Error {
+    use nix::libc::{ENODEV, ENXIO};
+    use std::error;
+    use std::fmt;
+    use std::io;
+    use std::path::PathBuf;
+
+    /// Everything that can go wrong while probing a JBOD.
+    ///
+    /// The library never exits the process, it returns a `JbodError` and the
+    /// caller (the CLI or the exporter) decides how to report it.
+    #[derive(Debug)]
+    pub enum JbodError {
+        /// An external tool we rely on is not installed, example: lsscsi
+        MissingTool(String),
+        /// We are not allowed to open a device or write a sysfs file.
+        PermissionDenied(String),
+        /// A device answered with data we cannot decode.
+        Parse(String),
+        /// A device or an enclosure went away while we were probing it.
+        DeviceVanished(String),
+        /// The sysfs class folder does not exist or is empty.
+        SysfsMissing(PathBuf),
+        /// The caller asked for something that cannot be done.
+        InvalidArgument(String),
+        /// The enclosure accepted a control page but did not apply it.
+        ControlRejected(String),
+        /// Any other I/O error on the given device or file.
+        Io(String, io::Error),
+    }
+
+    impl JbodError {
+        /// Returns the `JbodError` for an I/O error on `what`, a device or a path.
+        ///
+        /// # Example
+        /// ```
+        /// use std::io;
+        /// use jbod::JbodError;
+        ///
+        /// let err = io::Error::from(io::ErrorKind::PermissionDenied);
+        /// assert!(matches!(JbodError::from_io("/dev/sg0", err), JbodError::PermissionDenied(_)));
+        /// ```
+        pub fn from_io<S: Into<String>>(what: S, err: io::Error) -> Self {
+            let what = what.into();
+            match (err.kind(), err.raw_os_error()) {
+                (io::ErrorKind::PermissionDenied, _) => JbodError::PermissionDenied(what),
+                (io::ErrorKind::NotFound, _) => JbodError::DeviceVanished(what),
+                (_, Some(ENXIO)) | (_, Some(ENODEV)) => JbodError::DeviceVanished(what),
+                (io::ErrorKind::InvalidData, _) => JbodError::Parse(format!("{}: {}", what, err)),
+                _ => JbodError::Io(what, err),
+            }
+        }
+    }
+
+    impl fmt::Display for JbodError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                JbodError::MissingTool(tool) => write!(f, "{} is not installed", tool),
+                JbodError::PermissionDenied(what) => write!(f, "permission denied: {}", what),
+                JbodError::Parse(msg) => write!(f, "failed to parse {}", msg),
+                JbodError::DeviceVanished(what) => write!(f, "device {} not found", what),
+                JbodError::SysfsMissing(path) => {
+                    write!(f, "{} is missing or empty", path.display())
+                }
+                JbodError::InvalidArgument(msg) => write!(f, "{}", msg),
+                JbodError::ControlRejected(msg) => write!(f, "control not applied: {}", msg),
+                JbodError::Io(what, err) => write!(f, "{}: {}", what, err),
+            }
+        }
+    }
+
+    impl error::Error for JbodError {
+        fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+            match self {
+                JbodError::Io(_, err) => Some(err),
+                _ => None,
+            }
+        }
+    }
+}
@@ -30,19 +30,20 @@
 
 #[allow(non_snake_case)]
 pub mod Util {
-    use colored::*;
     use std::fs;
     use std::io;
     use std::path::Path;
-    use std::process::exit;
+
+    use crate::utils::error::Error::JbodError;
 
     pub const LSSCSI: &str = "/usr/bin/lsscsi";
-    pub const SG_INQ: &str = "/usr/bin/sg_inq";
-    pub const SCSI_TEMP: &str = "/usr/bin/scsi_temperature";
-    pub const SG_MAP: &str = "/usr/bin/sg_map";
-    pub const SG_SES: &str = "/usr/bin/sg_ses";
-    pub const SGINFO: &str = "/usr/bin/sginfo";
     pub const JBOD_EXPORTER: &str = "/usr/bin/prometheus-jbod-exporter";
+    pub const SYSFS_ROOT: &str = "/sys";
+    pub const MDSTAT: &str = "/proc/mdstat";
+    pub const DEFAULT_CONCURRENCY: usize = 16;
+    /// The number of times an enclosure status is read back, one second
+    /// apart, to check a control page was applied.
+    pub const CONTROL_VERIFY_RETRIES: usize = 5;
 
     /// Returns an enum with true or false if a directory is empty
     ///
@@ -50,28 +51,22 @@ pub mod Util {
     ///
     /// # Arguments
     ///
-    /// * `path` - a string reference
-    pub fn is_folder_empty(path: &str) -> io::Result<bool> {
+    /// * `path` - a path reference
+    pub fn is_folder_empty<P: AsRef<Path>>(path: P) -> io::Result<bool> {
         Ok(fs::read_dir(path)?.next().is_none())
     }
 
-    /// Verify if sysclass folder exists or otherwise exit and
-    /// provide software alternatives to jbod-rs.
-    pub fn verify_sysclass_folder(path: &str) {
-        if is_folder_empty(path).unwrap_or(false) {
-            println!(
-                "{} {} {}",
-                "==> ".red().bold(),
-                "jbod-rs not supported in this machine".bold(),
-                " <==".red().bold()
-            );
-            print!("{}", ":: ".bold().red());
-            println!("Use one of these alternatives: ");
-            println!(" - lsscsi");
-            println!(" - sas-lsi-tools");
-            println!(" - sas2ircu-status");
-            println!(" - sg3-utils");
-            exit(1);
+    /// Verify if sysclass folder exists and is not empty, otherwise the
+    /// machine has no enclosure driver loaded and jbod-rs is not supported.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - a path reference, example: /sys/class/enclosure
+    ///
+    pub fn verify_sysclass_folder<P: AsRef<Path>>(path: P) -> Result<(), JbodError> {
+        match is_folder_empty(&path) {
+            Ok(false) => Ok(()),
+            _ => Err(JbodError::SysfsMissing(path.as_ref().to_path_buf())),
         }
     }
 
@@ -81,39 +76,19 @@ pub mod Util {
     ///
     /// # Arguments
     ///
-    /// * `path` - a string reference
+    /// * `path` - a path reference
     ///
-    pub fn path_exists(path: &str) -> bool {
-        Path::new(path).exists()
+    pub fn path_exists<P: AsRef<Path>>(path: P) -> bool {
+        path.as_ref().exists()
     }
 
     /// Verify if all needed binaries are installed
-    pub fn verify_binary_needed() {
-        let mut binaries_not_found = Vec::new();
+    pub fn verify_binary_needed() -> Result<(), JbodError> {
         if !path_exists(LSSCSI) {
-            binaries_not_found.push("lsscsi");
-        }
-        if !path_exists(SG_INQ) {
-            binaries_not_found.push("sg3-utils");
-        }
-        if !path_exists(SCSI_TEMP) {
-            binaries_not_found.push("sg3-utils: scsi_temperature");
+            return Err(JbodError::MissingTool(LSSCSI.to_string()));
         }
 
-        if !binaries_not_found.is_empty() {
-            println!(
-                "{} {} {}",
-                "==> ".blue().bold(),
-                "Packages missing".bold(),
-                " <==".blue().bold()
-            );
-            for err in binaries_not_found {
-                print!("{}", ":: ".bold().red());
-                print!("Install package ");
-                println!("{}", err.red().bold().blink());
-            }
-            exit(1);
-        }
+        Ok(())
     }
 
     /// Returns true or false for every each character
@@ -125,8 +100,10 @@ pub mod Util {
     /// * `s` - a string
     /// # Example:
     /// ```
-    /// assert(!is_string_numeric(&"123");
-    /// assert(!is_string_numeric(String::from("Pikatchu"));
+    /// use jbod::Util::is_string_numeric;
+    ///
+    /// assert!(is_string_numeric("123"));
+    /// assert!(!is_string_numeric(String::from("Pikatchu")));
     /// ```
     pub fn is_string_numeric<S>(s: S) -> bool
     where
@@ -0,0 +1,226 @@
+/*-
+ * SPDX-License-Identifier: BSD-2-Clause
+ *
+ * BSD 2-Clause License
+ *
+ * Copyright (c) 2021-2023, Gandi S.A.S.
+ * All rights reserved.
+ *
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice, this
+ *    list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+ * AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+ * IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+ * FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+ * DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+ * SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+ * CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+ * OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+#[allow(non_snake_case)]
+pub mod MdStat {
+    use std::fs;
+
+    use crate::utils::error::Error::JbodError;
+    use crate::utils::helper::Util::MDSTAT;
+    use crate::utils::runner::Runner::Context;
+
+    /// A device of an md array, example: sdb1[1](F)
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct MdMember {
+        /// The block device name, example: sdb1
+        pub name: String,
+        /// The descriptor number of the device in the array.
+        pub role: usize,
+        /// The position of the device in the array, from sysfs, `None` for a
+        /// spare or when sysfs was not read.
+        pub slot: Option<usize>,
+        pub spare: bool,
+        pub faulty: bool,
+    }
+
+    /// An md array as reported by /proc/mdstat.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct MdArray {
+        /// The array name, example: md0
+        pub name: String,
+        pub active: bool,
+        /// The RAID level, example: raid6, empty for an inactive array.
+        pub level: String,
+        /// The number of devices the array is made of.
+        pub raid_disks: usize,
+        /// The state of every device of the array, example: "UU_U", `_` is missing.
+        pub status: String,
+        /// A device is being rebuilt.
+        pub recovering: bool,
+        pub members: Vec<MdMember>,
+    }
+
+    impl MdArray {
+        /// Returns the number of devices missing in the array.
+        pub fn missing(&self) -> usize {
+            self.status.chars().filter(|c| *c == '_').count()
+        }
+
+        /// Returns true if the array lost some redundancy but still works.
+        pub fn degraded(&self) -> bool {
+            self.missing() > 0 && !self.failed()
+        }
+
+        /// Returns true if the array lost more devices than its redundancy,
+        /// a raid10 is only considered failed once half of it is missing.
+        pub fn failed(&self) -> bool {
+            let redundancy = match self.level.as_str() {
+                "raid1" => self.raid_disks.saturating_sub(1),
+                "raid4" | "raid5" => 1,
+                "raid6" => 2,
+                "raid10" => self.raid_disks / 2,
+                _ => 0,
+            };
+            !self.active || self.missing() > redundancy
+        }
+
+        /// Returns true if `member` is the device being rebuilt, the one
+        /// sitting at a missing position while the array recovers.
+        ///
+        /// Without the position from sysfs the descriptor number is used,
+        /// they are the same unless devices were added and removed.
+        pub fn rebuilding(&self, member: &MdMember) -> bool {
+            let position = member.slot.unwrap_or(member.role);
+            self.recovering
+                && !member.spare
+                && !member.faulty
+                && self.status.chars().nth(position) == Some('_')
+        }
+    }
+
+    /// Returns the member of an array from its /proc/mdstat token,
+    /// example: sdb1[1](F)
+    fn parse_member(token: &str) -> Option<MdMember> {
+        let (name, rest) = token.split_once('[')?;
+        let (role, flags) = rest.split_once(']')?;
+        Some(MdMember {
+            name: name.to_string(),
+            role: role.parse().ok()?,
+            slot: None,
+            spare: flags.contains("(S)"),
+            faulty: flags.contains("(F)"),
+        })
+    }
+
+    /// Returns the arrays described by the content of /proc/mdstat.
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - the content of /proc/mdstat
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::MdStat;
+    ///
+    /// let arrays = MdStat::parse("Personalities : [raid6] [raid5] [raid4]
+    /// md0 : active raid5 sdd[2] sde[4](S) sdc[1] sdb[0]
+    ///       1953260544 blocks super 1.2 level 5, 512k chunk, algorithm 2 [3/2] [UU_]
+    ///       [=>...................]  recovery =  8.6% (84058112/976630272) finish=93.2min
+    ///
+    /// unused devices: <none>
+    /// ");
+    ///
+    /// assert_eq!(arrays[0].name, "md0");
+    /// assert_eq!(arrays[0].members.len(), 4);
+    /// assert!(arrays[0].degraded() && !arrays[0].failed());
+    /// assert!(arrays[0].rebuilding(&arrays[0].members[0]));
+    /// assert!(arrays[0].members[1].spare);
+    /// ```
+    ///
+    pub fn parse(content: &str) -> Vec<MdArray> {
+        let mut arrays: Vec<MdArray> = Vec::new();
+
+        for line in content.lines() {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            if tokens.len() >= 3 && tokens[0].starts_with("md") && tokens[1] == ":" {
+                let active = tokens[2] == "active";
+                let mut devices = &tokens[3..];
+                // The level is missing on an inactive array, "(read-only)"
+                // or "(auto-read-only)" can sit before it.
+                while devices.first().is_some_and(|t| t.starts_with('(')) {
+                    devices = &devices[1..];
+                }
+                let level = match devices.first() {
+                    Some(level) if !level.contains('[') => {
+                        devices = &devices[1..];
+                        level.to_string()
+                    }
+                    _ => String::new(),
+                };
+                let members: Vec<MdMember> = devices.iter().filter_map(|t| parse_member(t)).collect();
+                let in_use = members.iter().filter(|m| !m.spare).count();
+                arrays.push(MdArray {
+                    name: tokens[0].to_string(),
+                    active,
+                    level,
+                    raid_disks: in_use,
+                    status: "U".repeat(in_use),
+                    recovering: false,
+                    members,
+                });
+                continue;
+            }
+
+            let Some(array) = arrays.last_mut() else {
+                continue;
+            };
+            if !line.starts_with(char::is_whitespace) {
+                continue;
+            }
+            if let Some(position) = tokens.iter().position(|t| t.starts_with('[') && t.contains('/')) {
+                let counts = tokens[position].trim_matches(|c| c == '[' || c == ']');
+                if let Some(raid_disks) = counts.split('/').next().and_then(|n| n.parse().ok()) {
+                    array.raid_disks = raid_disks;
+                }
+                if let Some(status) = tokens.get(position + 1) {
+                    array.status = status.trim_matches(|c| c == '[' || c == ']').to_string();
+                }
+            }
+            if line.contains("recovery") {
+                array.recovering = true;
+            }
+        }
+
+        arrays
+    }
+
+    /// Returns the md arrays of the host, read from /proc/mdstat, with the
+    /// position of every member read from sysfs, example:
+    /// /sys/block/md0/md/dev-sdb/slot
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - the context holding the sysfs root
+    ///
+    pub fn read(ctx: &Context) -> Result<Vec<MdArray>, JbodError> {
+        let content = fs::read_to_string(MDSTAT).map_err(|err| JbodError::from_io(MDSTAT, err))?;
+        let mut arrays = parse(&content);
+        for array in arrays.iter_mut() {
+            let md = ctx.sysfs_root.join("block").join(&array.name).join("md");
+            for member in array.members.iter_mut() {
+                member.slot = fs::read_to_string(md.join(format!("dev-{}", member.name)).join("slot"))
+                    .ok()
+                    .and_then(|slot| slot.trim().parse().ok());
+            }
+        }
+
+        Ok(arrays)
+    }
+}
@@ -0,0 +1,251 @@
+/*-
+ * SPDX-License-Identifier: BSD-2-Clause
+ *
+ * BSD 2-Clause License
+ *
+ * Copyright (c) 2021-2023, Gandi S.A.S.
+ * All rights reserved.
+ *
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice, this
+ *    list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+ * AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+ * IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+ * FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+ * DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+ * SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+ * CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+ * OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+#[allow(non_snake_case)]
+pub mod Output {
+    use serde::Serialize;
+
+    use crate::jbod::disks::DiskShelf::Disk;
+    use crate::jbod::enclosure::BackPlane::{Enclosure, EnclosureFan};
+    use crate::utils::error::Error::JbodError;
+
+    /// The version of the JSON document, it is bumped whenever a field is
+    /// renamed or removed, adding a field keeps the same version.
+    pub const SCHEMA_VERSION: u32 = 2;
+
+    /// The JSON document printed by `jbod list --output json`.
+    #[derive(Debug, Serialize)]
+    pub struct Inventory<'a> {
+        pub schema_version: u32,
+        pub enclosures: &'a [Enclosure],
+    }
+
+    /// Returns the enclosures, with their disks and FAN, as a JSON document.
+    ///
+    /// # Arguments
+    ///
+    /// * `enclosures` - the enclosures to print
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::Output;
+    ///
+    /// let json = Output::to_json(&[]).unwrap();
+    /// assert_eq!(json, "{\n  \"schema_version\": 2,\n  \"enclosures\": []\n}");
+    /// ```
+    ///
+    pub fn to_json(enclosures: &[Enclosure]) -> Result<String, JbodError> {
+        let inventory = Inventory {
+            schema_version: SCHEMA_VERSION,
+            enclosures,
+        };
+
+        serde_json::to_string_pretty(&inventory).map_err(|err| JbodError::Parse(err.to_string()))
+    }
+
+    /// The columns of the disk inventory, in the order of the `Disk` fields.
+    pub const DISK_COLUMNS: &[&str] = &[
+        "enclosure",
+        "slot",
+        "device_path",
+        "device_map",
+        "temperature",
+        "vendor",
+        "model",
+        "serial",
+        "fw_revision",
+        "wwn",
+        "led_locate_path",
+        "led_fault_path",
+        "flags",
+    ];
+
+    /// Returns the value of a disk field as printed in the CSV/TSV output,
+    /// a missing value is an empty string, or `None` for an unknown field.
+    ///
+    /// # Arguments
+    ///
+    /// * `disk` - the disk to read the field from
+    /// * `field` - one of `DISK_COLUMNS`
+    ///
+    pub fn disk_field(disk: &Disk, field: &str) -> Option<String> {
+        let path = |p: &Option<std::path::PathBuf>| {
+            p.as_ref().map(|p| p.to_string_lossy().to_string()).unwrap_or_default()
+        };
+
+        let value = match field {
+            "enclosure" => disk.enclosure.clone(),
+            "slot" => disk.slot.to_string(),
+            "device_path" => disk.device_path.clone(),
+            "device_map" => disk.device_map.clone().unwrap_or_default(),
+            "temperature" => disk.temperature.map(|t| t.to_string()).unwrap_or_default(),
+            "vendor" => disk.vendor.clone(),
+            "model" => disk.model.clone(),
+            "serial" => disk.serial.clone().unwrap_or_default(),
+            "fw_revision" => disk.fw_revision.clone(),
+            "wwn" => disk.wwn.clone().unwrap_or_default(),
+            "led_locate_path" => path(&disk.led_locate_path),
+            "led_fault_path" => path(&disk.led_fault_path),
+            "flags" => disk.flags.map(|f| f.flags().join(",")).unwrap_or_default(),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// The fields of an enclosure, in the order of the `Enclosure` fields.
+    pub const ENCLOSURE_COLUMNS: &[&str] = &[
+        "slot",
+        "device_path",
+        "vendor",
+        "model",
+        "revision",
+        "serial",
+        "wwn",
+    ];
+
+    /// Returns the value of an enclosure field, a missing value is an empty
+    /// string, or `None` for an unknown field.
+    ///
+    /// # Arguments
+    ///
+    /// * `enclosure` - the enclosure to read the field from
+    /// * `field` - one of `ENCLOSURE_COLUMNS`
+    ///
+    pub fn enclosure_field(enclosure: &Enclosure, field: &str) -> Option<String> {
+        let value = match field {
+            "slot" => enclosure.slot.clone(),
+            "device_path" => enclosure.device_path.clone(),
+            "vendor" => enclosure.vendor.clone(),
+            "model" => enclosure.model.clone(),
+            "revision" => enclosure.revision.clone(),
+            "serial" => enclosure.serial.clone().unwrap_or_default(),
+            "wwn" => enclosure.wwn.clone().unwrap_or_default(),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// The fields of a FAN, in the order of the `EnclosureFan` fields.
+    pub const FAN_COLUMNS: &[&str] = &[
+        "slot",
+        "serial",
+        "description",
+        "index",
+        "status",
+        "speed",
+        "speed_code",
+        "requested_on",
+        "off",
+    ];
+
+    /// Returns the value of a FAN field, a missing value is an empty string,
+    /// or `None` for an unknown field.
+    ///
+    /// # Arguments
+    ///
+    /// * `fan` - the FAN to read the field from
+    /// * `field` - one of `FAN_COLUMNS`
+    ///
+    pub fn fan_field(fan: &EnclosureFan, field: &str) -> Option<String> {
+        let value = match field {
+            "slot" => fan.slot.clone(),
+            "serial" => fan.serial.clone().unwrap_or_default(),
+            "description" => fan.description.clone(),
+            "index" => fan.index.clone(),
+            "status" => fan.status.to_string(),
+            "speed" => fan.speed.to_string(),
+            "speed_code" => fan.speed_code.to_string(),
+            "requested_on" => fan.flags.requested_on.to_string(),
+            "off" => fan.flags.off.to_string(),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Returns a field escaped for the given separator.
+    ///
+    /// CSV fields are quoted as in RFC 4180 when needed, TSV cannot quote so
+    /// tabs and new lines are replaced by a space.
+    fn escape(value: &str, separator: char) -> String {
+        if separator == '\t' {
+            value.replace(['\t', '\n', '\r'], " ")
+        } else if value.contains([separator, '"', '\n', '\r']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_string()
+        }
+    }
+
+    /// Returns the disks as delimited text with a header row.
+    ///
+    /// # Arguments
+    ///
+    /// * `disks` - the disks to print
+    /// * `columns` - the columns to print, an empty slice prints `DISK_COLUMNS`
+    /// * `separator` - ',' for CSV or '\t' for TSV
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::Output;
+    ///
+    /// let csv = Output::to_delimited(&[], &["slot", "serial"], ',').unwrap();
+    /// assert_eq!(csv, "slot,serial\n");
+    /// assert!(Output::to_delimited(&[], &["color"], ',').is_err());
+    /// ```
+    ///
+    pub fn to_delimited(
+        disks: &[Disk],
+        columns: &[&str],
+        separator: char,
+    ) -> Result<String, JbodError> {
+        let columns = if columns.is_empty() { DISK_COLUMNS } else { columns };
+        if let Some(unknown) = columns.iter().find(|c| !DISK_COLUMNS.contains(c)) {
+            return Err(JbodError::InvalidArgument(format!(
+                "unknown column {}, expected one of: {}",
+                unknown,
+                DISK_COLUMNS.join(",")
+            )));
+        }
+
+        let separator_str = separator.to_string();
+        let mut output = columns.join(&separator_str);
+        output.push('\n');
+        for disk in disks {
+            let row: Vec<String> = columns
+                .iter()
+                .map(|c| escape(&disk_field(disk, c).unwrap_or_default(), separator))
+                .collect();
+            output.push_str(&row.join(&separator_str));
+            output.push('\n');
+        }
+
+        Ok(output)
+    }
+}
@@ -0,0 +1,308 @@
+/*-
+ * SPDX-License-Identifier: BSD-2-Clause
+ *
+ * BSD 2-Clause License
+ *
+ * Copyright (c) 2021-2023, Gandi S.A.S.
+ * All rights reserved.
+ *
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice, this
+ *    list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+ * AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+ * IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+ * FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+ * DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+ * SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+ * CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+ * OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+#[allow(non_snake_case)]
+pub mod Runner {
+    use std::collections::HashMap;
+    use std::io;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+    use std::sync::{Arc, Mutex};
+
+    use crate::utils::helper::Util::{DEFAULT_CONCURRENCY, SYSFS_ROOT};
+    use crate::utils::sgio::SgIo;
+
+    /// Backend used to run the external tools (sg3_utils, lsscsi) and the
+    /// SCSI commands sent to the devices.
+    ///
+    /// Every probe in `BackPlane` and `DiskShelf` goes through this trait,
+    /// so the parsers can be fed with captured output instead of a real JBOD.
+    pub trait CommandRunner: Send + Sync {
+        /// Returns the standard output of `program` called with `args`.
+        fn run(&self, program: &str, args: &[&str]) -> io::Result<String>;
+
+        /// Returns up to `len` bytes read from `device` after sending `cdb`.
+        fn scsi_in(&self, device: &str, cdb: &[u8], len: usize) -> io::Result<Vec<u8>>;
+
+        /// Sends `cdb` to `device` followed by `data`.
+        fn scsi_out(&self, device: &str, cdb: &[u8], data: &[u8]) -> io::Result<()>;
+    }
+
+    /// A shared runner, the caller keeps a handle on it, example: to check
+    /// what a `FixtureRunner` was sent.
+    impl<R: CommandRunner + ?Sized> CommandRunner for Arc<R> {
+        fn run(&self, program: &str, args: &[&str]) -> io::Result<String> {
+            (**self).run(program, args)
+        }
+
+        fn scsi_in(&self, device: &str, cdb: &[u8], len: usize) -> io::Result<Vec<u8>> {
+            (**self).scsi_in(device, cdb, len)
+        }
+
+        fn scsi_out(&self, device: &str, cdb: &[u8], data: &[u8]) -> io::Result<()> {
+            (**self).scsi_out(device, cdb, data)
+        }
+    }
+
+    /// Runs the commands on the host, this is what the CLI and the exporter use.
+    pub struct SystemRunner;
+
+    impl CommandRunner for SystemRunner {
+        fn run(&self, program: &str, args: &[&str]) -> io::Result<String> {
+            let output = Command::new(program).args(args).output()?;
+            Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        }
+
+        fn scsi_in(&self, device: &str, cdb: &[u8], len: usize) -> io::Result<Vec<u8>> {
+            SgIo::data_in(device, cdb, len)
+        }
+
+        fn scsi_out(&self, device: &str, cdb: &[u8], data: &[u8]) -> io::Result<()> {
+            SgIo::data_out(device, cdb, data)
+        }
+    }
+
+    /// A SCSI command recorded by the `FixtureRunner`: the device, the CDB and the data.
+    pub type ScsiCommand = (String, Vec<u8>, Vec<u8>);
+
+    /// Replays the output previously captured for a given command line,
+    /// and the data previously captured for a given SCSI command.
+    ///
+    /// The data sent to the devices is recorded, it can be checked with `sent`.
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::utils::runner::Runner::{CommandRunner, FixtureRunner};
+    ///
+    /// let runner = FixtureRunner::new().with_output("/usr/bin/lsscsi", &["-g"], "output");
+    /// assert_eq!(runner.run("/usr/bin/lsscsi", &["-g"]).unwrap(), "output");
+    /// assert!(runner.run("/usr/bin/sg_map", &[]).is_err());
+    ///
+    /// let runner = FixtureRunner::new()
+    ///     .with_scsi_data("/dev/sg0", &[0x1c, 0x01, 0x02], &[0x02, 0x00])
+    ///     .with_scsi_data_after_send("/dev/sg0", &[0x1c, 0x01, 0x02], &[0x02, 0x01]);
+    /// assert_eq!(runner.scsi_in("/dev/sg0", &[0x1c, 0x01, 0x02], 2).unwrap(), vec![0x02, 0x00]);
+    ///
+    /// runner.scsi_out("/dev/sg0", &[0x1d, 0x10], &[0x02, 0x00]).unwrap();
+    /// assert_eq!(runner.sent(), vec![("/dev/sg0".to_string(), vec![0x1d, 0x10], vec![0x02, 0x00])]);
+    /// assert_eq!(runner.scsi_in("/dev/sg0", &[0x1c, 0x01, 0x02], 2).unwrap(), vec![0x02, 0x01]);
+    /// ```
+    #[derive(Default)]
+    pub struct FixtureRunner {
+        outputs: HashMap<String, String>,
+        scsi_data: Vec<ScsiCommand>,
+        scsi_data_after_send: Vec<ScsiCommand>,
+        sent: Mutex<Vec<ScsiCommand>>,
+        received: Mutex<Vec<(String, Vec<u8>)>>,
+    }
+
+    /// Returns the key used to store a command line in the `FixtureRunner`.
+    fn command_line(program: &str, args: &[&str]) -> String {
+        let mut line = program.to_string();
+        for arg in args {
+            line.push(' ');
+            line.push_str(arg);
+        }
+        line
+    }
+
+    impl FixtureRunner {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Registers the `output` returned when `program` runs with `args`.
+        pub fn with_output(mut self, program: &str, args: &[&str], output: &str) -> Self {
+            self.outputs
+                .insert(command_line(program, args), output.to_string());
+            self
+        }
+
+        /// Registers the `data` returned for every CDB sent to `device`
+        /// starting with `cdb_prefix`, example: `&[0x1c, 0x01, 0x02]` for the
+        /// enclosure status page whatever the allocation length is.
+        pub fn with_scsi_data(mut self, device: &str, cdb_prefix: &[u8], data: &[u8]) -> Self {
+            self.scsi_data
+                .push((device.to_string(), cdb_prefix.to_vec(), data.to_vec()));
+            self
+        }
+
+        /// Registers the `data` returned like `with_scsi_data`, once something
+        /// was sent to `device`, example: the enclosure status page read back
+        /// after a control page.
+        pub fn with_scsi_data_after_send(
+            mut self,
+            device: &str,
+            cdb_prefix: &[u8],
+            data: &[u8],
+        ) -> Self {
+            self.scsi_data_after_send
+                .push((device.to_string(), cdb_prefix.to_vec(), data.to_vec()));
+            self
+        }
+
+        /// Returns the device, the CDB and the data of every `scsi_out` call.
+        pub fn sent(&self) -> Vec<ScsiCommand> {
+            self.sent.lock().map(|sent| sent.clone()).unwrap_or_default()
+        }
+
+        /// Returns the device and the CDB of every `scsi_in` call, answered
+        /// or not.
+        pub fn received(&self) -> Vec<(String, Vec<u8>)> {
+            self.received
+                .lock()
+                .map(|received| received.clone())
+                .unwrap_or_default()
+        }
+    }
+
+    impl CommandRunner for FixtureRunner {
+        fn run(&self, program: &str, args: &[&str]) -> io::Result<String> {
+            let line = command_line(program, args);
+            self.outputs.get(&line).cloned().ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, format!("no fixture for: {}", line))
+            })
+        }
+
+        fn scsi_in(&self, device: &str, cdb: &[u8], len: usize) -> io::Result<Vec<u8>> {
+            if let Ok(mut received) = self.received.lock() {
+                received.push((device.to_string(), cdb.to_vec()));
+            }
+            let sent = self.sent().iter().any(|(dev, _, _)| dev == device);
+            let after_send = self.scsi_data_after_send.iter().filter(|_| sent);
+            after_send
+                .chain(self.scsi_data.iter())
+                .find(|(dev, prefix, _)| dev == device && cdb.starts_with(prefix))
+                .map(|(_, _, data)| data[..data.len().min(len)].to_vec())
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("no fixture for: {} {:02x?}", device, cdb),
+                    )
+                })
+        }
+
+        fn scsi_out(&self, device: &str, cdb: &[u8], data: &[u8]) -> io::Result<()> {
+            if let Ok(mut sent) = self.sent.lock() {
+                sent.push((device.to_string(), cdb.to_vec(), data.to_vec()));
+            }
+            Ok(())
+        }
+    }
+
+    /// The environment a probe runs against.
+    ///
+    /// `Context::default()` runs the real tools on the host and reads `/sys`.
+    pub struct Context {
+        pub runner: Box<dyn CommandRunner>,
+        /// The sysfs mount point, it can point to a tree captured from another machine.
+        pub sysfs_root: PathBuf,
+        /// The maximum number of devices probed at the same time.
+        pub concurrency: usize,
+        /// Whether SCSI commands are sent to the devices, `false` when the
+        /// sysfs root is a tree captured from another machine: the local
+        /// /dev/sgN are not the devices of that tree.
+        pub send_scsi: bool,
+    }
+
+    impl Context {
+        pub fn new(runner: Box<dyn CommandRunner>) -> Self {
+            Context {
+                runner,
+                sysfs_root: PathBuf::from(SYSFS_ROOT),
+                concurrency: DEFAULT_CONCURRENCY,
+                send_scsi: true,
+            }
+        }
+
+        /// Returns the context reading sysfs from `root` instead of `/sys`,
+        /// no SCSI command is sent unless `root` is `/sys`.
+        pub fn with_sysfs_root<P: Into<PathBuf>>(mut self, root: P) -> Self {
+            self.sysfs_root = root.into();
+            self.send_scsi = self.sysfs_root == Path::new(SYSFS_ROOT);
+            self
+        }
+
+        /// Returns the context sending SCSI commands or not, whatever the
+        /// sysfs root is, example: a fake tree probed with a `FixtureRunner`.
+        pub fn with_send_scsi(mut self, send_scsi: bool) -> Self {
+            self.send_scsi = send_scsi;
+            self
+        }
+
+        /// Returns the context probing up to `concurrency` devices at the
+        /// same time, 1 probes them one after the other.
+        pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+            self.concurrency = concurrency.max(1);
+            self
+        }
+
+        /// Returns the path of a class under the sysfs root, example: /sys/class/enclosure
+        pub fn sys_class(&self, class: &str) -> PathBuf {
+            self.sysfs_root.join("class").join(class)
+        }
+
+        /// Returns an error if the context must not send SCSI commands.
+        fn check_send_scsi(&self, device: &str) -> io::Result<()> {
+            if self.send_scsi {
+                Ok(())
+            } else {
+                Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!(
+                        "{}: no SCSI command sent for the sysfs tree {}",
+                        device,
+                        self.sysfs_root.display()
+                    ),
+                ))
+            }
+        }
+
+        /// Sends `cdb` to `device` with the runner and returns the data read,
+        /// see `CommandRunner::scsi_in`.
+        pub fn scsi_in(&self, device: &str, cdb: &[u8], len: usize) -> io::Result<Vec<u8>> {
+            self.check_send_scsi(device)?;
+            self.runner.scsi_in(device, cdb, len)
+        }
+
+        /// Sends `cdb` and `data` to `device` with the runner, see
+        /// `CommandRunner::scsi_out`.
+        pub fn scsi_out(&self, device: &str, cdb: &[u8], data: &[u8]) -> io::Result<()> {
+            self.check_send_scsi(device)?;
+            self.runner.scsi_out(device, cdb, data)
+        }
+    }
+
+    impl Default for Context {
+        fn default() -> Self {
+            Context::new(Box::new(SystemRunner))
+        }
+    }
+}
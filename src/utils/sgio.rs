@@ -0,0 +1,167 @@
+/*-
+ * SPDX-License-Identifier: BSD-2-Clause
+ *
+ * BSD 2-Clause License
+ *
+ * Copyright (c) 2021-2023, Gandi S.A.S.
+ * All rights reserved.
+ *
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice, this
+ *    list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+ * AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+ * IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+ * FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+ * DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+ * SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+ * CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+ * OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+#[allow(non_snake_case)]
+pub mod SgIo {
+    use nix::libc::{c_int, c_uchar, c_uint, c_ushort, c_void};
+    use std::fs::OpenOptions;
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+
+    /// The SG_IO ioctl number from <scsi/sg.h>.
+    const SG_IO: u32 = 0x2285;
+    const SG_INTERFACE_ID: c_int = 'S' as c_int;
+    const SG_DXFER_TO_DEV: c_int = -2;
+    const SG_DXFER_FROM_DEV: c_int = -3;
+    /// Timeout in milliseconds, enclosures can be slow to answer diagnostic pages.
+    const SG_TIMEOUT: c_uint = 20_000;
+    const SENSE_LEN: usize = 32;
+    const SCSI_CHECK_CONDITION: c_uchar = 0x02;
+
+    /// The `sg_io_hdr_t` structure from <scsi/sg.h>.
+    #[repr(C)]
+    struct SgIoHdr {
+        interface_id: c_int,
+        dxfer_direction: c_int,
+        cmd_len: c_uchar,
+        mx_sb_len: c_uchar,
+        iovec_count: c_ushort,
+        dxfer_len: c_uint,
+        dxferp: *mut c_void,
+        cmdp: *const c_uchar,
+        sbp: *mut c_uchar,
+        timeout: c_uint,
+        flags: c_uint,
+        pack_id: c_int,
+        usr_ptr: *mut c_void,
+        status: c_uchar,
+        masked_status: c_uchar,
+        msg_status: c_uchar,
+        sb_len_wr: c_uchar,
+        host_status: c_ushort,
+        driver_status: c_ushort,
+        resid: c_int,
+        duration: c_uint,
+        info: c_uint,
+    }
+
+    nix::ioctl_readwrite_bad!(sg_io, SG_IO, SgIoHdr);
+
+    /// Returns a message with the sense key, asc and ascq of a sense buffer.
+    fn sense_to_string(sense: &[u8]) -> String {
+        let (key, asc, ascq) = match sense.first().map(|b| b & 0x7f) {
+            Some(0x72) | Some(0x73) if sense.len() > 3 => (sense[1] & 0x0f, sense[2], sense[3]),
+            Some(0x70) | Some(0x71) if sense.len() > 13 => (sense[2] & 0x0f, sense[12], sense[13]),
+            _ => return "no sense data".to_string(),
+        };
+        format!("sense key 0x{:x}, asc 0x{:02x}, ascq 0x{:02x}", key, asc, ascq)
+    }
+
+    /// Sends `cdb` to `device` and moves `data` in the given direction.
+    ///
+    /// Returns the number of bytes actually transferred.
+    fn execute(device: &str, cdb: &[u8], direction: c_int, data: &mut [u8]) -> io::Result<usize> {
+        let file = OpenOptions::new().read(true).write(true).open(device)?;
+        let mut sense = [0u8; SENSE_LEN];
+        let mut hdr = SgIoHdr {
+            interface_id: SG_INTERFACE_ID,
+            dxfer_direction: direction,
+            cmd_len: cdb.len() as c_uchar,
+            mx_sb_len: SENSE_LEN as c_uchar,
+            iovec_count: 0,
+            dxfer_len: data.len() as c_uint,
+            dxferp: data.as_mut_ptr() as *mut c_void,
+            cmdp: cdb.as_ptr(),
+            sbp: sense.as_mut_ptr(),
+            timeout: SG_TIMEOUT,
+            flags: 0,
+            pack_id: 0,
+            usr_ptr: std::ptr::null_mut(),
+            status: 0,
+            masked_status: 0,
+            msg_status: 0,
+            sb_len_wr: 0,
+            host_status: 0,
+            driver_status: 0,
+            resid: 0,
+            duration: 0,
+            info: 0,
+        };
+
+        // The buffers referenced by `hdr` outlive the ioctl call.
+        unsafe { sg_io(file.as_raw_fd(), &mut hdr) }.map_err(io::Error::from)?;
+
+        if hdr.status == SCSI_CHECK_CONDITION {
+            let sense_len = (hdr.sb_len_wr as usize).min(SENSE_LEN);
+            return Err(io::Error::other(format!(
+                "{}: {}",
+                device,
+                sense_to_string(&sense[..sense_len])
+            )));
+        }
+        if hdr.status != 0 || hdr.host_status != 0 || (hdr.driver_status & 0x0f) != 0 {
+            return Err(io::Error::other(format!(
+                "{}: scsi status 0x{:x}, host status 0x{:x}, driver status 0x{:x}",
+                device, hdr.status, hdr.host_status, hdr.driver_status
+            )));
+        }
+
+        Ok(data.len().saturating_sub(hdr.resid.max(0) as usize))
+    }
+
+    /// Returns the data read from `device` after sending `cdb`.
+    ///
+    /// # Arguments
+    ///
+    /// * `device` - the scsi generic device, example: /dev/sg9
+    /// * `cdb` - the command descriptor block
+    /// * `len` - the allocation length set in the `cdb`
+    ///
+    pub fn data_in(device: &str, cdb: &[u8], len: usize) -> io::Result<Vec<u8>> {
+        let mut data = vec![0u8; len];
+        let transferred = execute(device, cdb, SG_DXFER_FROM_DEV, &mut data)?;
+        data.truncate(transferred);
+        Ok(data)
+    }
+
+    /// Sends `cdb` to `device` followed by `data`.
+    ///
+    /// # Arguments
+    ///
+    /// * `device` - the scsi generic device, example: /dev/sg9
+    /// * `cdb` - the command descriptor block
+    /// * `data` - the parameter list, its length is set in the `cdb`
+    ///
+    pub fn data_out(device: &str, cdb: &[u8], data: &[u8]) -> io::Result<()> {
+        let mut data = data.to_vec();
+        execute(device, cdb, SG_DXFER_TO_DEV, &mut data)?;
+        Ok(())
+    }
+}
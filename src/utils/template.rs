@@ -0,0 +1,394 @@
+/*-
+ * SPDX-License-Identifier: BSD-2-Clause
+ *
+ * BSD 2-Clause License
+ *
+ * Copyright (c) 2021-2023, Gandi S.A.S.
+ * All rights reserved.
+ *
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice, this
+ *    list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+ * AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+ * IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+ * FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+ * DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+ * SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+ * CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+ * OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+#[allow(non_snake_case)]
+pub mod Template {
+    use crate::jbod::disks::DiskShelf::Disk;
+    use crate::jbod::enclosure::BackPlane::{Enclosure, EnclosureFan};
+    use crate::utils::error::Error::JbodError;
+    use crate::utils::output::Output;
+
+    /// What a template is rendered for, one line per enclosure, per disk or
+    /// per FAN.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Scope {
+        Enclosure,
+        Disk,
+        Fan,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Align {
+        Left,
+        Right,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    enum Node {
+        Text(String),
+        Field {
+            name: String,
+            align: Align,
+            width: usize,
+        },
+        /// `{disks}...{end}`, repeated for every disk of the enclosure.
+        Disks(Vec<Node>),
+        /// `{fans}...{end}`, repeated for every FAN of the enclosure.
+        Fans(Vec<Node>),
+    }
+
+    /// A user defined output, example: `{enclosure}:{slot} {device_map} {serial}`
+    ///
+    /// * `{field}` prints a field of the current scope, `{enclosure.field}`
+    ///   prints a field of the enclosure from a disk or a FAN.
+    /// * `{field:<10}` and `{field:>10}` pad the field to 10 characters,
+    ///   left or right aligned, `{field:10}` is the same as `{field:<10}`.
+    /// * `{disks}...{end}` and `{fans}...{end}` repeat their content for
+    ///   every disk or FAN of an enclosure.
+    /// * `{{`, `}}`, `\n` and `\t` print `{`, `}`, a new line and a tab.
+    ///
+    /// # Example
+    /// ```
+    /// use jbod::BackPlane::Enclosure;
+    /// use jbod::DiskShelf::Disk;
+    /// use jbod::Template::{Scope, Template};
+    ///
+    /// let disk = Disk {
+    ///     enclosure: "0:0:0:0".to_string(), slot: 7, device_path: "/dev/sg7".to_string(),
+    ///     device_map: Some("/dev/sdh".to_string()), temperature: Some(36),
+    ///     vendor: "HGST".to_string(), model: "HUH721212AL5200".to_string(),
+    ///     serial: Some("8DGX1ABC".to_string()), fw_revision: "A925".to_string(),
+    ///     ..Default::default()
+    /// };
+    /// let enclosure = Enclosure {
+    ///     slot: "0:0:0:0".to_string(), device_path: "/dev/sg0".to_string(),
+    ///     vendor: "HGST".to_string(), model: "H4060-J".to_string(), revision: "2033".to_string(),
+    ///     serial: Some("USWSJ03918EZ0069".to_string()), disks: vec![disk], ..Default::default()
+    /// };
+    ///
+    /// let template = Template::parse("{enclosure}:{slot:>3} {device_map} {serial}", Scope::Disk).unwrap();
+    /// assert_eq!(template.render(&[enclosure.clone()]), "0:0:0:0:  7 /dev/sdh 8DGX1ABC\n");
+    ///
+    /// let template = Template::parse("{model}\\n{disks}  {slot:<3}|{enclosure.serial}\\n{end}", Scope::Enclosure).unwrap();
+    /// assert_eq!(template.render(&[enclosure]), "H4060-J\n  7  |USWSJ03918EZ0069\n");
+    ///
+    /// assert!(Template::parse("{color}", Scope::Disk).is_err());
+    /// assert!(Template::parse("{disks}{slot}", Scope::Enclosure).is_err());
+    /// ```
+    #[derive(Debug, Clone)]
+    pub struct Template {
+        nodes: Vec<Node>,
+        scope: Scope,
+    }
+
+    fn invalid(msg: String) -> JbodError {
+        JbodError::InvalidArgument(format!("--format: {}", msg))
+    }
+
+    /// Returns true if `name` is a field of `scope`.
+    fn is_field(scope: Scope, name: &str) -> bool {
+        if let Some(field) = name.strip_prefix("enclosure.") {
+            return scope != Scope::Enclosure && Output::ENCLOSURE_COLUMNS.contains(&field);
+        }
+        match scope {
+            Scope::Enclosure => Output::ENCLOSURE_COLUMNS.contains(&name),
+            Scope::Disk => Output::DISK_COLUMNS.contains(&name),
+            Scope::Fan => Output::FAN_COLUMNS.contains(&name),
+        }
+    }
+
+    /// Returns the field node for a tag like `slot`, `slot:<10` or `slot:>10`.
+    fn parse_field(tag: &str, scope: Scope) -> Result<Node, JbodError> {
+        let (name, spec) = match tag.split_once(':') {
+            Some((name, spec)) => (name, spec),
+            None => (tag, ""),
+        };
+        if !is_field(scope, name) {
+            return Err(invalid(format!("unknown field {{{}}}", name)));
+        }
+
+        let (align, width) = match spec.as_bytes().first() {
+            Some(b'<') => (Align::Left, &spec[1..]),
+            Some(b'>') => (Align::Right, &spec[1..]),
+            _ => (Align::Left, spec),
+        };
+        let width = if width.is_empty() {
+            0
+        } else {
+            width
+                .parse()
+                .map_err(|_| invalid(format!("bad padding in {{{}}}", tag)))?
+        };
+
+        Ok(Node::Field {
+            name: name.to_string(),
+            align,
+            width,
+        })
+    }
+
+    /// Returns the nodes until `{end}` or the end of the template.
+    fn parse_nodes(
+        chars: &mut std::iter::Peekable<std::str::Chars>,
+        scope: Scope,
+        in_block: bool,
+    ) -> Result<Vec<Node>, JbodError> {
+        let mut nodes = Vec::new();
+        let mut text = String::new();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '}' => return Err(invalid("unmatched }".to_string())),
+                '\\' => match chars.next() {
+                    Some('n') => text.push('\n'),
+                    Some('t') => text.push('\t'),
+                    Some(other) => text.push(other),
+                    None => text.push('\\'),
+                },
+                '{' => {
+                    let mut tag = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => tag.push(c),
+                            None => return Err(invalid("unclosed {".to_string())),
+                        }
+                    }
+                    if !text.is_empty() {
+                        nodes.push(Node::Text(std::mem::take(&mut text)));
+                    }
+                    match tag.as_str() {
+                        "end" if in_block => return Ok(nodes),
+                        "end" => return Err(invalid("{end} without a block".to_string())),
+                        "disks" | "fans" if scope != Scope::Enclosure || in_block => {
+                            return Err(invalid(format!(
+                                "{{{}}} is only allowed per enclosure, use it with -e",
+                                tag
+                            )));
+                        }
+                        "disks" => nodes.push(Node::Disks(parse_nodes(chars, Scope::Disk, true)?)),
+                        "fans" => nodes.push(Node::Fans(parse_nodes(chars, Scope::Fan, true)?)),
+                        _ => nodes.push(parse_field(&tag, scope)?),
+                    }
+                }
+                _ => text.push(c),
+            }
+        }
+
+        if in_block {
+            return Err(invalid("missing {end}".to_string()));
+        }
+        if !text.is_empty() {
+            nodes.push(Node::Text(text));
+        }
+        Ok(nodes)
+    }
+
+    /// Returns the number of characters printed on a terminal, the ANSI
+    /// escape sequences used for colors do not count.
+    fn visible_width(s: &str) -> usize {
+        let mut width = 0;
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                for c in chars.by_ref() {
+                    if c.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            } else {
+                width += 1;
+            }
+        }
+        width
+    }
+
+    /// The objects a node can read its fields from.
+    struct Item<'a> {
+        enclosure: &'a Enclosure,
+        disk: Option<&'a Disk>,
+        fan: Option<&'a EnclosureFan>,
+    }
+
+    impl Item<'_> {
+        fn field(&self, name: &str) -> String {
+            let value = if let Some(field) = name.strip_prefix("enclosure.") {
+                Output::enclosure_field(self.enclosure, field)
+            } else if let Some(disk) = self.disk {
+                Output::disk_field(disk, name)
+            } else if let Some(fan) = self.fan {
+                Output::fan_field(fan, name)
+            } else {
+                Output::enclosure_field(self.enclosure, name)
+            };
+            value.unwrap_or_default()
+        }
+    }
+
+    impl Template {
+        /// Returns the parsed template, or an error pointing at the first
+        /// unknown field or unbalanced block.
+        ///
+        /// # Arguments
+        ///
+        /// * `template` - the template given by the user
+        /// * `scope` - what the template is rendered for
+        ///
+        pub fn parse(template: &str, scope: Scope) -> Result<Template, JbodError> {
+            let nodes = parse_nodes(&mut template.chars().peekable(), scope, false)?;
+            Ok(Template { nodes, scope })
+        }
+
+        /// Returns true if the template iterates over the disks.
+        pub fn uses_disks(&self) -> bool {
+            self.nodes.iter().any(|n| matches!(n, Node::Disks(_)))
+        }
+
+        /// Returns true if the template iterates over the FAN.
+        pub fn uses_fans(&self) -> bool {
+            self.nodes.iter().any(|n| matches!(n, Node::Fans(_)))
+        }
+
+        /// Returns the template rendered for every enclosure, disk or FAN
+        /// depending on its scope, one per line.
+        ///
+        /// # Arguments
+        ///
+        /// * `enclosures` - the enclosures, with their disks and FAN
+        ///
+        pub fn render(&self, enclosures: &[Enclosure]) -> String {
+            self.render_with(enclosures, |_field, value| value.to_string())
+        }
+
+        /// Same as `render` but every field goes through `style` first, it
+        /// gets the field name and its value and returns the text to print,
+        /// colors included. The padding ignores the colors.
+        ///
+        /// # Arguments
+        ///
+        /// * `enclosures` - the enclosures, with their disks and FAN
+        /// * `style` - called for every field
+        ///
+        /// # Example
+        /// ```
+        /// use jbod::Template::{Scope, Template};
+        ///
+        /// let template = Template::parse("{slot}", Scope::Enclosure).unwrap();
+        /// assert_eq!(template.render(&[]), "");
+        /// ```
+        pub fn render_with<F>(&self, enclosures: &[Enclosure], style: F) -> String
+        where
+            F: Fn(&str, &str) -> String,
+        {
+            let mut output = String::new();
+            for enclosure in enclosures {
+                let mut items = Vec::new();
+                match self.scope {
+                    Scope::Enclosure => items.push(Item {
+                        enclosure,
+                        disk: None,
+                        fan: None,
+                    }),
+                    Scope::Disk => items.extend(enclosure.disks.iter().map(|disk| Item {
+                        enclosure,
+                        disk: Some(disk),
+                        fan: None,
+                    })),
+                    Scope::Fan => items.extend(enclosure.fans.iter().map(|fan| Item {
+                        enclosure,
+                        disk: None,
+                        fan: Some(fan),
+                    })),
+                }
+
+                for item in items {
+                    render_nodes(&self.nodes, &item, &style, &mut output);
+                    if !output.ends_with('\n') {
+                        output.push('\n');
+                    }
+                }
+            }
+            output
+        }
+    }
+
+    fn render_nodes<F>(nodes: &[Node], item: &Item, style: &F, output: &mut String)
+    where
+        F: Fn(&str, &str) -> String,
+    {
+        for node in nodes {
+            match node {
+                Node::Text(text) => output.push_str(text),
+                Node::Field { name, align, width } => {
+                    let value = style(name, &item.field(name));
+                    let padding = " ".repeat(width.saturating_sub(visible_width(&value)));
+                    match align {
+                        Align::Left => {
+                            output.push_str(&value);
+                            output.push_str(&padding);
+                        }
+                        Align::Right => {
+                            output.push_str(&padding);
+                            output.push_str(&value);
+                        }
+                    }
+                }
+                Node::Disks(block) => {
+                    for disk in item.enclosure.disks.iter() {
+                        let disk_item = Item {
+                            enclosure: item.enclosure,
+                            disk: Some(disk),
+                            fan: None,
+                        };
+                        render_nodes(block, &disk_item, style, output);
+                    }
+                }
+                Node::Fans(block) => {
+                    for fan in item.enclosure.fans.iter() {
+                        let fan_item = Item {
+                            enclosure: item.enclosure,
+                            disk: None,
+                            fan: Some(fan),
+                        };
+                        render_nodes(block, &fan_item, style, output);
+                    }
+                }
+            }
+        }
+    }
+}
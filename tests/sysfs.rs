@@ -0,0 +1,440 @@
+/*-
+ * SPDX-License-Identifier: BSD-2-Clause
+ *
+ * BSD 2-Clause License
+ *
+ * Copyright (c) 2021-2023, Gandi S.A.S.
+ * All rights reserved.
+ *
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice, this
+ *    list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+ * AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+ * IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+ * FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+ * DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+ * SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+ * CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+ * OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+//! Probes the fake sysfs tree under tests/fixtures/sysfs, captured from a
+//! single enclosure with four slots:
+//!
+//! * Slot 00, 01 and 10 are linked to their disk by the `ses` driver, the
+//!   disk of Slot 10 has no block device.
+//! * Slot 02 is not linked, its disk is only known by SAS address.
+//!
+//! In /sys/class/block sda is held by md0, sdb by nothing, the first
+//! partition of sdd by a dm device and its second partition by md1.
+//!
+//! The tree alone is probed like a capture from another machine, without
+//! SCSI commands. The enclosure pages are answered by a `FixtureRunner`
+//! with `live_context`.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use jbod::utils::runner::Runner::{CommandRunner, Context, FixtureRunner};
+use jbod::Ses::SlotStatus;
+use jbod::SlotMap::{self, SlotState};
+use jbod::{BackPlane, DiskShelf, MdStat, Util};
+
+/// Returns the sysfs root of the fixture tree.
+fn sysfs_root() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/sysfs")
+}
+
+/// Returns a runner listing the enclosure of the fixture tree.
+fn lsscsi_runner() -> FixtureRunner {
+    let sysfsroot = format!("--sysfsroot={}", sysfs_root().display());
+    FixtureRunner::new().with_output(
+        Util::LSSCSI,
+        &["-g", &sysfsroot],
+        "[0:0:0:0]    enclosu HGST     H4060-J          2033  -          /dev/sg0\n",
+    )
+}
+
+/// Returns an additional element status descriptor of an array device
+/// slot, with one phy attached to `sas_address`.
+fn slot_descriptor(element: u8, device_slot: u8, sas_address: u64) -> Vec<u8> {
+    [
+        &[0x16, 0x22, 0x00, element, 0x01, 0x00, 0x00, device_slot][..],
+        &[0x00; 12],
+        &sas_address.to_be_bytes(),
+        &[0x00; 8],
+    ]
+    .concat()
+}
+
+/// Returns a runner listing the enclosure of the fixture tree, with six
+/// array device slots reporting the SAS address of their disk.
+fn sas_runner() -> FixtureRunner {
+    let config = [
+        &[0x01, 0x00, 0x00, 0x30, 0x00, 0x00, 0x00, 0x01][..],
+        &[0x11, 0x00, 0x01, 0x24],
+        &[0x50, 0x00, 0xcc, 0xa0, 0x5a, 0x7b, 0x1c, 0x3e],
+        b"HGST    H4060-J         2033",
+        &[0x17, 0x06, 0x00, 0x00],
+    ]
+    .concat();
+    let descriptors = [
+        // Slot 00 is already linked by the ses driver.
+        slot_descriptor(0, 0, 0x5000cca26b1ac3a1),
+        slot_descriptor(2, 2, 0x5000cca26b1ac3a9),
+        // An end device without scsi device.
+        slot_descriptor(3, 3, 0x5000cca26b1ac3aa),
+        // An address with no end device.
+        slot_descriptor(5, 5, 0x5000cca26b1ac3ff),
+    ]
+    .concat();
+    let mut additional = vec![0x0a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01];
+    additional[2..4].copy_from_slice(&(descriptors.len() as u16 + 4).to_be_bytes());
+    additional.extend(descriptors);
+
+    lsscsi_runner()
+        .with_scsi_data("/dev/sg0", &[0x1c, 0x01, 0x01], &config)
+        .with_scsi_data("/dev/sg0", &[0x1c, 0x01, 0x0a], &additional)
+}
+
+/// Returns an enclosure status page with six array device slots, the
+/// first one reports `first`, the third one is being rebuilt and the last
+/// two slots are empty.
+fn status_page(first: [u8; 4]) -> Vec<u8> {
+    let slots = [
+        [0x00, 0x00, 0x00, 0x00],
+        first,
+        [0x01, 0x00, 0x00, 0x00],
+        [0x01, 0x02, 0x00, 0x00],
+        [0x01, 0x00, 0x00, 0x00],
+        [0x05, 0x00, 0x00, 0x00],
+        [0x05, 0x00, 0x00, 0x00],
+    ];
+    let mut status = vec![0x02, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x01];
+    status.extend(slots.concat());
+    status
+}
+
+/// Returns `sas_runner` with the enclosure status page, the first slot is
+/// a hot spare.
+fn status_runner() -> FixtureRunner {
+    let status = status_page([0x01, 0x20, 0x00, 0x00]);
+    sas_runner().with_scsi_data("/dev/sg0", &[0x1c, 0x01, 0x02], &status)
+}
+
+/// Returns the context probing the fixture tree with `runner`, as a tree
+/// captured from another machine.
+fn context<R: CommandRunner + 'static>(runner: R) -> Context {
+    Context::new(Box::new(runner)).with_sysfs_root(sysfs_root())
+}
+
+/// Returns the context probing the fixture tree with `runner` answering
+/// the SCSI commands, as the sysfs of the local machine.
+fn live_context<R: CommandRunner + 'static>(runner: R) -> Context {
+    context(runner).with_send_scsi(true)
+}
+
+#[test]
+fn get_disks_per_enclosure_from_sysfs() {
+    let ctx = context(lsscsi_runner());
+    let enclosures = BackPlane::get_enclosure(&ctx).unwrap();
+    assert_eq!(enclosures.len(), 1);
+
+    let disks = DiskShelf::get_disks_per_enclosure(&ctx, &enclosures[0]).unwrap();
+    let slots: Vec<u32> = disks.iter().map(|disk| disk.slot).collect();
+    assert_eq!(slots, vec![0, 1, 10]);
+
+    let disk = &disks[0];
+    assert_eq!(disk.enclosure, "0:0:0:0");
+    assert_eq!(disk.device_path, "/dev/sg1");
+    assert_eq!(disk.device_map.as_deref(), Some("/dev/sda"));
+    assert_eq!(disk.vendor, "HGST");
+    assert_eq!(disk.model, "HUH721212AL4200");
+    assert_eq!(disk.fw_revision, "A3D0");
+    assert_eq!(disk.serial.as_deref(), Some("8PG2ABCD"));
+    assert_eq!(disk.wwn.as_deref(), Some("0x5000cca26b1ac3a0"));
+    assert_eq!(disk.temperature, None);
+    let component = sysfs_root().join("class/enclosure/0:0:0:0/Slot 00");
+    assert_eq!(disk.led_locate_path, Some(component.join("locate")));
+    assert_eq!(disk.led_fault_path, Some(component.join("fault")));
+
+    assert_eq!(disks[1].device_map.as_deref(), Some("/dev/sdb"));
+    assert_eq!(disks[2].device_path, "/dev/sg3");
+    assert_eq!(disks[2].device_map, None);
+}
+
+#[test]
+fn captured_tree_sends_no_scsi_command() {
+    // The runner knows /dev/sg0 and /dev/sg1, they are local devices that
+    // have nothing to do with the tree.
+    let runner = Arc::new(
+        status_runner()
+            .with_scsi_data("/dev/sg0", &[0x12], b"local enclosure")
+            .with_scsi_data("/dev/sg1", &[0x12], b"local disk")
+            .with_scsi_data("/dev/sg1", &[0x4d], &[0x0d, 0x00, 0x00, 0x00]),
+    );
+    let ctx = context(Arc::clone(&runner));
+    let enclosures = DiskShelf::jbod_inventory(&ctx).unwrap();
+
+    let enclosure = &enclosures[0];
+    assert_eq!(enclosure.vendor, "HGST");
+    assert_eq!(enclosure.model, "H4060-J");
+    assert_eq!(enclosure.revision, "2033");
+    assert_eq!(enclosure.serial.as_deref(), Some("USWSJ03918EZ0069"));
+    assert_eq!(enclosure.wwn.as_deref(), Some("0x5000cca05a7b1c3e"));
+    let disk = &enclosure.disks[0];
+    assert_eq!(disk.model, "HUH721212AL4200");
+    assert_eq!(disk.serial.as_deref(), Some("8PG2ABCD"));
+    assert_eq!(disk.wwn.as_deref(), Some("0x5000cca26b1ac3a0"));
+    assert_eq!(disk.temperature, None);
+    assert_eq!(disk.flags, None);
+    assert!(enclosure.slots.is_empty());
+
+    assert!(runner.received().is_empty(), "{:02x?}", runner.received());
+    assert!(runner.sent().is_empty());
+}
+
+#[test]
+fn get_disks_per_enclosure_without_the_enclosure() {
+    let ctx = context(lsscsi_runner());
+    let mut enclosure = BackPlane::get_enclosure(&ctx).unwrap().remove(0);
+    enclosure.slot = "9:0:0:0".to_string();
+
+    assert!(DiskShelf::get_disks_per_enclosure(&ctx, &enclosure).is_err());
+}
+
+#[test]
+fn get_disks_per_enclosure_concurrently() {
+    let probe = |ctx: &Context| {
+        let enclosures = BackPlane::get_enclosure(ctx).unwrap();
+        DiskShelf::get_disks_per_enclosure(ctx, &enclosures[0])
+            .unwrap()
+            .into_iter()
+            .map(|disk| (disk.slot, disk.device_path))
+            .collect::<Vec<(u32, String)>>()
+    };
+    let sequential = probe(&context(lsscsi_runner()).with_concurrency(1));
+
+    for concurrency in [2, 4, 16] {
+        let ctx = context(lsscsi_runner()).with_concurrency(concurrency);
+        assert_eq!(probe(&ctx), sequential);
+    }
+    assert_eq!(
+        sequential,
+        vec![
+            (0, "/dev/sg1".to_string()),
+            (1, "/dev/sg2".to_string()),
+            (10, "/dev/sg3".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn get_disks_per_enclosure_by_sas_address() {
+    let ctx = live_context(sas_runner());
+    let enclosures = BackPlane::get_enclosure(&ctx).unwrap();
+    let disks = DiskShelf::get_disks_per_enclosure(&ctx, &enclosures[0]).unwrap();
+    let slots: Vec<u32> = disks.iter().map(|disk| disk.slot).collect();
+    assert_eq!(slots, vec![0, 1, 2, 10]);
+
+    // The linked disk is not replaced by the SAS address.
+    assert_eq!(disks[0].device_path, "/dev/sg1");
+    let disk = &disks[2];
+    assert_eq!(disk.device_path, "/dev/sg4");
+    assert_eq!(disk.device_map.as_deref(), Some("/dev/sdd"));
+    assert_eq!(disk.serial.as_deref(), Some("8PG2ABCG"));
+    let component = sysfs_root().join("class/enclosure/0:0:0:0/Slot 02");
+    assert_eq!(disk.led_locate_path, Some(component.join("locate")));
+    assert_eq!(disk.led_fault_path, Some(component.join("fault")));
+}
+
+#[test]
+fn get_disks_per_enclosure_by_sas_address_without_ses_driver() {
+    let ctx = live_context(sas_runner());
+    let mut enclosure = BackPlane::get_enclosure(&ctx).unwrap().remove(0);
+    enclosure.slot = "9:0:0:0".to_string();
+    let disks = DiskShelf::get_disks_per_enclosure(&ctx, &enclosure).unwrap();
+
+    // Without enclosure components every resolved address is a disk, the
+    // unknown addresses are skipped.
+    let slots: Vec<(u32, &str)> = disks
+        .iter()
+        .map(|disk| (disk.slot, disk.device_path.as_str()))
+        .collect();
+    assert_eq!(slots, vec![(2, "/dev/sg4")]);
+    assert_eq!(disks[0].enclosure, "9:0:0:0");
+    assert_eq!(disks[0].led_locate_path, None);
+}
+
+#[test]
+fn jbod_inventory_without_enclosure_pages() {
+    let ctx = context(lsscsi_runner());
+    let enclosures = DiskShelf::jbod_inventory(&ctx).unwrap();
+
+    // The enclosure does not answer, the disks found in sysfs are kept.
+    let slots: Vec<u32> = enclosures[0].disks.iter().map(|disk| disk.slot).collect();
+    assert_eq!(slots, vec![0, 1, 10]);
+    assert!(enclosures[0].slots.is_empty());
+    assert!(enclosures[0].fans.is_empty());
+    assert_eq!(enclosures[0].errors.len(), 1);
+}
+
+#[test]
+fn jbod_inventory_with_enclosure_pages() {
+    let ctx = live_context(status_runner());
+    let enclosure = DiskShelf::jbod_inventory(&ctx).unwrap().remove(0);
+
+    assert!(enclosure.errors.is_empty());
+    let slots: Vec<u32> = enclosure.disks.iter().map(|disk| disk.slot).collect();
+    assert_eq!(slots, vec![0, 1, 2, 10]);
+    let numbers: Vec<u32> = enclosure.slots.iter().map(|slot| slot.number).collect();
+    assert_eq!(numbers, vec![0, 1, 2, 3, 4, 5]);
+    assert!(enclosure.disks[0].flags.is_some_and(|f| f.hot_spare));
+    assert!(enclosure.disks[1].flags.is_some_and(|f| !f.hot_spare));
+    // The disk of Slot 10 has no slot element.
+    assert_eq!(enclosure.disks[3].flags, None);
+}
+
+#[test]
+fn slot_map_shows_the_devices_the_os_does_not_see() {
+    let ctx = live_context(status_runner());
+    let enclosure = DiskShelf::jbod_inventory(&ctx).unwrap().remove(0);
+
+    let states: Vec<SlotState> = (0..6)
+        .map(|number| {
+            let disk = enclosure.disks.iter().find(|d| d.slot == number);
+            let bay = enclosure.slots.iter().find(|s| s.number == number).map(|s| s.state);
+            SlotMap::slot_state(disk, bay)
+        })
+        .collect();
+    // Slot 3 holds a device the OS has no disk for.
+    assert_eq!(
+        states,
+        vec![
+            SlotState::Present,
+            SlotState::Present,
+            SlotState::Present,
+            SlotState::NotResponding,
+            SlotState::Empty,
+            SlotState::Empty,
+        ]
+    );
+    assert!(SlotMap::render(&enclosure, false).contains("not-responding"));
+}
+
+/// The md arrays of the fixture tree, sda is the only device left of a
+/// mirror.
+const MDSTAT: &str = "Personalities : [raid1]
+md0 : active raid1 sda[0]
+      11718754304 blocks super 1.2 [2/1] [U_]
+
+unused devices: <none>
+";
+
+#[test]
+fn get_raid_slot_flags_skips_the_disks_held_by_other_devices() {
+    let ctx = live_context(status_runner());
+    let enclosure = DiskShelf::jbod_inventory(&ctx).unwrap().remove(0);
+    let flags = DiskShelf::get_raid_slot_flags(&ctx, &enclosure.disks, &MdStat::parse(MDSTAT));
+
+    let critical = SlotStatus {
+        in_critical_array: true,
+        ..SlotStatus::default()
+    };
+    assert_eq!(flags.requested, vec![(0, critical), (1, SlotStatus::default())]);
+    assert_eq!(flags.skipped, vec![(2, "/dev/sdd".to_string())]);
+}
+
+#[test]
+fn get_raid_slot_flags_of_a_partition_member() {
+    let ctx = live_context(status_runner());
+    let enclosure = DiskShelf::jbod_inventory(&ctx).unwrap().remove(0);
+    let mdstat = "Personalities : [raid1]
+md1 : active raid1 sdd2[0] sdb[1](S)
+      11718754304 blocks super 1.2 [2/1] [U_]
+
+unused devices: <none>
+";
+    let flags = DiskShelf::get_raid_slot_flags(&ctx, &enclosure.disks, &MdStat::parse(mdstat));
+
+    // sdd2 is a partition of the disk of slot 2, sda is in no array.
+    let critical = SlotStatus {
+        in_critical_array: true,
+        ..SlotStatus::default()
+    };
+    let spare = SlotStatus {
+        hot_spare: true,
+        ..SlotStatus::default()
+    };
+    assert_eq!(flags.requested, vec![(1, spare), (2, critical)]);
+    assert_eq!(flags.skipped, vec![(0, "/dev/sda".to_string())]);
+}
+
+#[test]
+fn sync_slot_array_flags_sends_the_slots_that_differ() {
+    let applied = status_page([0x01, 0x08, 0x00, 0x00]);
+    let runner = Arc::new(status_runner().with_scsi_data_after_send(
+        "/dev/sg0",
+        &[0x1c, 0x01, 0x02],
+        &applied,
+    ));
+    let ctx = live_context(Arc::clone(&runner));
+    let enclosure = DiskShelf::jbod_inventory(&ctx).unwrap().remove(0);
+    let flags = DiskShelf::get_raid_slot_flags(&ctx, &enclosure.disks, &MdStat::parse(MDSTAT));
+
+    let (changes, page) =
+        BackPlane::sync_slot_array_flags(&ctx, &enclosure, &flags.requested, false).unwrap();
+
+    // Slot 0 leaves the hot spares for a degraded mirror, the rebuild bit of
+    // slot 2 is left as it is.
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].slot.number, 0);
+    assert_eq!(changes[0].device_map.as_deref(), Some("/dev/sda"));
+    assert_eq!(changes[0].diff(), (vec!["in_critical_array"], vec!["hot_spare"]));
+    let expected = [
+        &[0x02, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x01][..],
+        &[0x00, 0x00, 0x00, 0x00],
+        &[0x80, 0x08, 0x00, 0x00],
+        &[0x00; 20],
+    ]
+    .concat();
+    assert_eq!(page, Some(expected.clone()));
+    assert_eq!(
+        runner.sent(),
+        vec![(
+            "/dev/sg0".to_string(),
+            vec![0x1d, 0x10, 0x00, 0x00, 0x24, 0x00],
+            expected
+        )]
+    );
+}
+
+#[test]
+fn sync_slot_array_flags_sends_nothing_when_in_sync() {
+    let runner = Arc::new(sas_runner().with_scsi_data(
+        "/dev/sg0",
+        &[0x1c, 0x01, 0x02],
+        &status_page([0x01, 0x08, 0x00, 0x00]),
+    ));
+    let ctx = live_context(Arc::clone(&runner));
+    let enclosure = DiskShelf::jbod_inventory(&ctx).unwrap().remove(0);
+    let flags = DiskShelf::get_raid_slot_flags(&ctx, &enclosure.disks, &MdStat::parse(MDSTAT));
+
+    for dry_run in [true, false] {
+        let (changes, page) =
+            BackPlane::sync_slot_array_flags(&ctx, &enclosure, &flags.requested, dry_run).unwrap();
+        assert!(changes.is_empty());
+        assert_eq!(page, None);
+    }
+    assert!(runner.sent().is_empty());
+}